@@ -13,6 +13,8 @@ where
     }
 }
 
+// `Display` cannot be implemented here, as it would conflict with the blanket `From` impl above.
+#[allow(clippy::to_string_trait_impl)]
 impl ToString for AnyError {
     fn to_string(&self) -> String {
         self.message.clone()
@@ -0,0 +1,99 @@
+mod mysql;
+mod postgres;
+mod sqlite;
+mod sqlite_url;
+
+pub use mysql::MysqlBackend;
+pub use postgres::PostgresBackend;
+pub use sqlite::{SqliteBackend, column_exists, table_exists};
+pub use sqlite_url::{SqliteMode, SqliteUrl};
+
+use crate::{AnyResult, Dialect};
+
+/// A connection to a database the migrations run against. The migrator only talks to databases
+/// through this trait, every dialect providing its own implementation.
+///
+/// Rows of the migrations table are identified by their id and kind (see
+/// [`crate::MIGRATION_KIND_VERSIONED`] and the other kinds), the seeds table by the seed names.
+///
+/// Backends are created by [`crate::cli::DatabaseUrl::connect`], which picks the implementation
+/// of the dialect of the URL. Connecting is not part of the trait, as every backend opens its
+/// own kind of URL: SQLite a [`SqliteUrl`] and the key of encrypted databases, the others a
+/// connection string.
+pub trait Backend {
+    fn dialect(&self) -> Dialect;
+
+    fn begin(&mut self) -> AnyResult<()>;
+
+    fn commit(&mut self) -> AnyResult<()>;
+
+    fn rollback(&mut self) -> AnyResult<()>;
+
+    /// Whether DDL statements run inside the transaction. Migrations on backends committing
+    /// implicitly on DDL, such as MySQL, are run statement by statement with their progress
+    /// recorded, see [`crate::MigrationProgress`].
+    fn transactional_ddl(&self) -> bool {
+        true
+    }
+
+    /// Keeps other migrators from changing the database until the transaction ends. Backends of
+    /// databases that serialize writers on their own do nothing.
+    fn lock(&mut self) -> AnyResult<()> {
+        Ok(())
+    }
+
+    /// Executes SQL made of any number of statements.
+    fn execute_batch(&mut self, sql: &str) -> AnyResult<()>;
+
+    /// Creates the migrations table if it does not exist, upgrading tables created by older
+    /// versions of the migrator.
+    fn create_migrations_table(&mut self) -> AnyResult<()>;
+
+    /// The id of the latest versioned row of the migrations table, `None` if there is none or
+    /// the table does not exist.
+    fn current_revision(&mut self) -> AnyResult<Option<String>>;
+
+    /// The ids and checksums of the rows of the given kind, from the oldest to the newest. Empty
+    /// if the table does not exist.
+    fn migration_records(&mut self, kind: &str) -> AnyResult<Vec<(String, Option<String>)>>;
+
+    fn insert_migration_record(
+        &mut self,
+        id: &str,
+        kind: &str,
+        checksum: Option<&str>,
+    ) -> AnyResult<()>;
+
+    fn delete_migration_records(&mut self, id: &str, kind: &str) -> AnyResult<()>;
+
+    /// Creates the seeds table if it does not exist.
+    fn create_seeds_table(&mut self) -> AnyResult<()>;
+
+    /// The names of the seeds that ran against the database.
+    fn seeded(&mut self) -> AnyResult<Vec<String>>;
+
+    /// Records that the seed ran, replacing an earlier record of it.
+    fn record_seed(&mut self, name: &str, checksum: &str) -> AnyResult<()>;
+
+    /// Loads an extension into the connection, for the migrations using it. Loading an extension
+    /// already loaded does nothing. Only SQLite supports loading extensions.
+    fn load_extension(&mut self, name: &str) -> AnyResult<()> {
+        Err(format!(
+            "Cannot load extension {}, only SQLite databases load extensions, not {}",
+            name,
+            self.dialect()
+        )
+        .into())
+    }
+
+    /// Gives access to the concrete backend, see [`dyn Backend::downcast_mut`].
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any;
+}
+
+impl dyn Backend + '_ {
+    /// The concrete backend, e.g. a [`SqliteBackend`] for Rust migrations that need the
+    /// underlying connection.
+    pub fn downcast_mut<T: Backend + 'static>(&mut self) -> Option<&mut T> {
+        self.as_any_mut().downcast_mut::<T>()
+    }
+}
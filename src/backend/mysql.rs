@@ -0,0 +1,196 @@
+use mysql::{Conn, Opts, prelude::Queryable};
+
+use super::Backend;
+use crate::{AnyResult, Dialect};
+
+/// How long to wait for another migrator to release the lock, in seconds.
+const LOCK_TIMEOUT: u32 = 24 * 60 * 60;
+
+/// The MySQL and MariaDB backend, for `mysql://` URLs. The migrations and seeds tables live in
+/// the database of the URL.
+///
+/// MySQL commits implicitly before and after DDL statements, so migrations are run statement by
+/// statement with their progress recorded (see [`crate::MigrationProgress`]), and a failed
+/// migration is resumed rather than rolled back.
+pub struct MysqlBackend {
+    conn: Conn,
+}
+
+impl MysqlBackend {
+    pub fn new(conn: Conn) -> Self {
+        Self { conn }
+    }
+
+    pub fn connect(url: &str) -> AnyResult<Self> {
+        let opts = Opts::from_url(url)?;
+        if opts.get_db_name().is_none() {
+            return Err(format!("The database URL does not name a database: {}", url).into());
+        }
+
+        Ok(Self::new(Conn::new(opts)?))
+    }
+
+    pub fn conn(&mut self) -> &mut Conn {
+        &mut self.conn
+    }
+
+    fn table_exists(&mut self, name: &str) -> AnyResult<bool> {
+        let count = self.conn.exec_first::<u64, _, _>(
+            "SELECT COUNT(*) FROM information_schema.tables
+            WHERE table_schema = DATABASE() AND table_name = ?",
+            (name,),
+        )?;
+
+        Ok(count.unwrap_or_default() > 0)
+    }
+}
+
+impl Backend for MysqlBackend {
+    fn dialect(&self) -> Dialect {
+        Dialect::Mysql
+    }
+
+    fn begin(&mut self) -> AnyResult<()> {
+        Ok(self.conn.query_drop("START TRANSACTION")?)
+    }
+
+    fn commit(&mut self) -> AnyResult<()> {
+        Ok(self.conn.query_drop("COMMIT")?)
+    }
+
+    fn rollback(&mut self) -> AnyResult<()> {
+        Ok(self.conn.query_drop("ROLLBACK")?)
+    }
+
+    fn transactional_ddl(&self) -> bool {
+        false
+    }
+
+    /// Takes a named lock for the database, held by the connection until it is closed.
+    fn lock(&mut self) -> AnyResult<()> {
+        let lock_sql = format!(
+            "SELECT GET_LOCK(CONCAT('{}.', DATABASE()), ?)",
+            crate::MIGRATIONS_TABLE_NAME
+        );
+
+        if self.conn.exec_first::<Option<i64>, _, _>(&lock_sql, (0,))? == Some(Some(1)) {
+            return Ok(());
+        }
+
+        eprintln!("Waiting for another migrator to finish with the database");
+        match self
+            .conn
+            .exec_first::<Option<i64>, _, _>(&lock_sql, (LOCK_TIMEOUT,))?
+        {
+            Some(Some(1)) => Ok(()),
+            _ => Err("Timed out waiting for another migrator to finish with the database".into()),
+        }
+    }
+
+    fn execute_batch(&mut self, sql: &str) -> AnyResult<()> {
+        Ok(self.conn.query_drop(sql)?)
+    }
+
+    fn create_migrations_table(&mut self) -> AnyResult<()> {
+        Ok(self.conn.query_drop(format!(
+            "CREATE TABLE IF NOT EXISTS `{}` (
+                seq BIGINT AUTO_INCREMENT PRIMARY KEY,
+                migrated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                id VARCHAR(255) NOT NULL,
+                kind VARCHAR(32) NOT NULL DEFAULT '{}',
+                checksum CHAR(64)
+            )",
+            crate::MIGRATIONS_TABLE_NAME,
+            crate::MIGRATION_KIND_VERSIONED
+        ))?)
+    }
+
+    fn current_revision(&mut self) -> AnyResult<Option<String>> {
+        if !self.table_exists(crate::MIGRATIONS_TABLE_NAME)? {
+            return Ok(None);
+        }
+
+        Ok(self.conn.exec_first(
+            format!(
+                "SELECT id FROM `{}` WHERE kind = ? ORDER BY seq DESC LIMIT 1",
+                crate::MIGRATIONS_TABLE_NAME
+            ),
+            (crate::MIGRATION_KIND_VERSIONED,),
+        )?)
+    }
+
+    fn migration_records(&mut self, kind: &str) -> AnyResult<Vec<(String, Option<String>)>> {
+        if !self.table_exists(crate::MIGRATIONS_TABLE_NAME)? {
+            return Ok(Vec::new());
+        }
+
+        Ok(self.conn.exec(
+            format!(
+                "SELECT id, checksum FROM `{}` WHERE kind = ? ORDER BY seq",
+                crate::MIGRATIONS_TABLE_NAME
+            ),
+            (kind,),
+        )?)
+    }
+
+    fn insert_migration_record(
+        &mut self,
+        id: &str,
+        kind: &str,
+        checksum: Option<&str>,
+    ) -> AnyResult<()> {
+        Ok(self.conn.exec_drop(
+            format!(
+                "INSERT INTO `{}` (id, kind, checksum) VALUES (?, ?, ?)",
+                crate::MIGRATIONS_TABLE_NAME
+            ),
+            (id, kind, checksum),
+        )?)
+    }
+
+    fn delete_migration_records(&mut self, id: &str, kind: &str) -> AnyResult<()> {
+        Ok(self.conn.exec_drop(
+            format!(
+                "DELETE FROM `{}` WHERE kind = ? AND id = ?",
+                crate::MIGRATIONS_TABLE_NAME
+            ),
+            (kind, id),
+        )?)
+    }
+
+    fn create_seeds_table(&mut self) -> AnyResult<()> {
+        Ok(self.conn.query_drop(format!(
+            "CREATE TABLE IF NOT EXISTS `{}` (
+                name VARCHAR(255) PRIMARY KEY,
+                checksum CHAR(64) NOT NULL,
+                seeded_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+            crate::SEEDS_TABLE_NAME
+        ))?)
+    }
+
+    fn seeded(&mut self) -> AnyResult<Vec<String>> {
+        if !self.table_exists(crate::SEEDS_TABLE_NAME)? {
+            return Ok(Vec::new());
+        }
+
+        Ok(self
+            .conn
+            .query(format!("SELECT name FROM `{}`", crate::SEEDS_TABLE_NAME))?)
+    }
+
+    fn record_seed(&mut self, name: &str, checksum: &str) -> AnyResult<()> {
+        Ok(self.conn.exec_drop(
+            format!(
+                "INSERT INTO `{}` (name, checksum) VALUES (?, ?)
+                ON DUPLICATE KEY UPDATE checksum = VALUES(checksum), seeded_at = CURRENT_TIMESTAMP",
+                crate::SEEDS_TABLE_NAME
+            ),
+            (name, checksum),
+        )?)
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
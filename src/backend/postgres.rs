@@ -0,0 +1,378 @@
+use postgres::Client;
+
+use super::{Backend, sqlite_url::decode_uri_component};
+use crate::{AnyResult, Dialect};
+
+/// The schema holding the migrations and seeds tables when the URL does not give one.
+const DEFAULT_SCHEMA: &str = "public";
+
+/// The PostgreSQL backend, for `postgres://` and `postgresql://` URLs.
+///
+/// The migrations and seeds tables are qualified with the schema of the `schema` URL parameter,
+/// e.g. `postgres://localhost/app?schema=billing`, so that several applications can share a
+/// database. DDL is transactional in PostgreSQL, so a failed migration leaves no trace.
+///
+/// Connections use TLS, as negotiated by the `sslmode` URL parameter, when the crate is built
+/// with the `postgres-tls` feature. Without it, URLs with `sslmode=require` are rejected.
+pub struct PostgresBackend {
+    client: Client,
+    schema: String,
+}
+
+impl PostgresBackend {
+    pub fn new(client: Client, schema: impl Into<String>) -> Self {
+        Self {
+            client,
+            schema: schema.into(),
+        }
+    }
+
+    /// Connects to the database of the URL, taking the `schema` parameter out of it before
+    /// handing it to the driver.
+    pub fn connect(url: &str) -> AnyResult<Self> {
+        let (url, schema) = split_schema_parameter(url)?;
+        let client = connect_client(&url)?;
+
+        Ok(Self::new(
+            client,
+            schema.unwrap_or_else(|| DEFAULT_SCHEMA.to_string()),
+        ))
+    }
+
+    pub fn client(&mut self) -> &mut Client {
+        &mut self.client
+    }
+
+    pub fn schema(&self) -> &str {
+        &self.schema
+    }
+
+    /// The schema-qualified, quoted name of the table.
+    fn table(&self, name: &str) -> String {
+        format!(
+            "{}.{}",
+            quote_identifier(&self.schema),
+            quote_identifier(name)
+        )
+    }
+
+    fn table_exists(&mut self, name: &str) -> AnyResult<bool> {
+        let row = self
+            .client
+            .query_one(
+                "SELECT EXISTS (
+                SELECT 1 FROM information_schema.tables WHERE table_schema = $1 AND table_name = $2
+            )",
+                &[&self.schema, &name],
+            )
+            .map_err(describe_error)?;
+
+        Ok(row.get(0))
+    }
+}
+
+impl Backend for PostgresBackend {
+    fn dialect(&self) -> Dialect {
+        Dialect::Postgres
+    }
+
+    fn begin(&mut self) -> AnyResult<()> {
+        Ok(self.client.batch_execute("BEGIN").map_err(describe_error)?)
+    }
+
+    fn commit(&mut self) -> AnyResult<()> {
+        Ok(self
+            .client
+            .batch_execute("COMMIT")
+            .map_err(describe_error)?)
+    }
+
+    fn rollback(&mut self) -> AnyResult<()> {
+        Ok(self
+            .client
+            .batch_execute("ROLLBACK")
+            .map_err(describe_error)?)
+    }
+
+    /// Takes a transaction-level advisory lock keyed on the schema, released when the
+    /// transaction ends.
+    fn lock(&mut self) -> AnyResult<()> {
+        let lock_sql = |function: &str| {
+            format!(
+                "SELECT {}(hashtext('{}'), hashtext($1))",
+                function,
+                crate::MIGRATIONS_TABLE_NAME
+            )
+        };
+
+        let row = self
+            .client
+            .query_one(&lock_sql("pg_try_advisory_xact_lock"), &[&self.schema])
+            .map_err(describe_error)?;
+        if row.get::<_, bool>(0) {
+            return Ok(());
+        }
+
+        eprintln!(
+            "Waiting for another migrator to finish with schema {}",
+            self.schema
+        );
+        self.client
+            .query_one(&lock_sql("pg_advisory_xact_lock"), &[&self.schema])
+            .map_err(describe_error)?;
+
+        Ok(())
+    }
+
+    fn execute_batch(&mut self, sql: &str) -> AnyResult<()> {
+        Ok(self.client.batch_execute(sql).map_err(describe_error)?)
+    }
+
+    fn create_migrations_table(&mut self) -> AnyResult<()> {
+        let sql = format!(
+            "CREATE SCHEMA IF NOT EXISTS {};
+            CREATE TABLE IF NOT EXISTS {} (
+                seq BIGSERIAL PRIMARY KEY,
+                migrated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                id TEXT NOT NULL,
+                kind TEXT NOT NULL DEFAULT '{}',
+                checksum TEXT
+            )",
+            quote_identifier(&self.schema),
+            self.table(crate::MIGRATIONS_TABLE_NAME),
+            crate::MIGRATION_KIND_VERSIONED
+        );
+
+        Ok(self.client.batch_execute(&sql).map_err(describe_error)?)
+    }
+
+    fn current_revision(&mut self) -> AnyResult<Option<String>> {
+        if !self.table_exists(crate::MIGRATIONS_TABLE_NAME)? {
+            return Ok(None);
+        }
+
+        let row = self
+            .client
+            .query_opt(
+                &format!(
+                    "SELECT id FROM {} WHERE kind = $1 ORDER BY seq DESC LIMIT 1",
+                    self.table(crate::MIGRATIONS_TABLE_NAME)
+                ),
+                &[&crate::MIGRATION_KIND_VERSIONED],
+            )
+            .map_err(describe_error)?;
+
+        Ok(row.map(|row| row.get(0)))
+    }
+
+    fn migration_records(&mut self, kind: &str) -> AnyResult<Vec<(String, Option<String>)>> {
+        if !self.table_exists(crate::MIGRATIONS_TABLE_NAME)? {
+            return Ok(Vec::new());
+        }
+
+        let rows = self
+            .client
+            .query(
+                &format!(
+                    "SELECT id, checksum FROM {} WHERE kind = $1 ORDER BY seq",
+                    self.table(crate::MIGRATIONS_TABLE_NAME)
+                ),
+                &[&kind],
+            )
+            .map_err(describe_error)?;
+
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    fn insert_migration_record(
+        &mut self,
+        id: &str,
+        kind: &str,
+        checksum: Option<&str>,
+    ) -> AnyResult<()> {
+        self.client
+            .execute(
+                &format!(
+                    "INSERT INTO {} (id, kind, checksum) VALUES ($1, $2, $3)",
+                    self.table(crate::MIGRATIONS_TABLE_NAME)
+                ),
+                &[&id, &kind, &checksum],
+            )
+            .map_err(describe_error)?;
+
+        Ok(())
+    }
+
+    fn delete_migration_records(&mut self, id: &str, kind: &str) -> AnyResult<()> {
+        self.client
+            .execute(
+                &format!(
+                    "DELETE FROM {} WHERE kind = $1 AND id = $2",
+                    self.table(crate::MIGRATIONS_TABLE_NAME)
+                ),
+                &[&kind, &id],
+            )
+            .map_err(describe_error)?;
+
+        Ok(())
+    }
+
+    fn create_seeds_table(&mut self) -> AnyResult<()> {
+        let sql = format!(
+            "CREATE SCHEMA IF NOT EXISTS {};
+            CREATE TABLE IF NOT EXISTS {} (
+                name TEXT PRIMARY KEY,
+                checksum TEXT NOT NULL,
+                seeded_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            )",
+            quote_identifier(&self.schema),
+            self.table(crate::SEEDS_TABLE_NAME)
+        );
+
+        Ok(self.client.batch_execute(&sql).map_err(describe_error)?)
+    }
+
+    fn seeded(&mut self) -> AnyResult<Vec<String>> {
+        if !self.table_exists(crate::SEEDS_TABLE_NAME)? {
+            return Ok(Vec::new());
+        }
+
+        let rows = self
+            .client
+            .query(
+                &format!("SELECT name FROM {}", self.table(crate::SEEDS_TABLE_NAME)),
+                &[],
+            )
+            .map_err(describe_error)?;
+
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    fn record_seed(&mut self, name: &str, checksum: &str) -> AnyResult<()> {
+        self.client
+            .execute(
+                &format!(
+                    "INSERT INTO {} (name, checksum) VALUES ($1, $2)
+                ON CONFLICT (name) DO UPDATE SET checksum = EXCLUDED.checksum, seeded_at = now()",
+                    self.table(crate::SEEDS_TABLE_NAME)
+                ),
+                &[&name, &checksum],
+            )
+            .map_err(describe_error)?;
+
+        Ok(())
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// The driver only says e.g. "db error" or "error connecting to server", the details are in the
+/// source of the error.
+fn describe_error(err: postgres::Error) -> String {
+    match (err.as_db_error(), std::error::Error::source(&err)) {
+        (Some(db_error), _) => db_error.to_string(),
+        (None, Some(source)) => format!("{}: {}", err, source),
+        (None, None) => err.to_string(),
+    }
+}
+
+/// Connects over TLS when the server supports it, or when `sslmode=require` demands it. The
+/// certificate of the server is verified against the root certificates of the system.
+#[cfg(feature = "postgres-tls")]
+fn connect_client(url: &str) -> AnyResult<Client> {
+    let connector = native_tls::TlsConnector::new()
+        .map_err(|err| format!("Cannot set up TLS to connect to PostgreSQL: {}", err))?;
+    let tls = postgres_native_tls::MakeTlsConnector::new(connector);
+
+    Ok(Client::connect(url, tls).map_err(describe_error)?)
+}
+
+#[cfg(not(feature = "postgres-tls"))]
+fn connect_client(url: &str) -> AnyResult<Client> {
+    let config = url.parse::<postgres::Config>().map_err(describe_error)?;
+    if config.get_ssl_mode() == postgres::config::SslMode::Require {
+        return Err(
+            "The URL requires TLS (sslmode=require), but the migrator was built without \
+            TLS support, rebuild it with the postgres-tls feature"
+                .into(),
+        );
+    }
+
+    Ok(Client::connect(url, postgres::NoTls).map_err(describe_error)?)
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// Removes the `schema` parameter from the query string of the URL, returning the remaining URL
+/// and the percent-decoded schema.
+fn split_schema_parameter(url: &str) -> Result<(String, Option<String>), String> {
+    let Some((base, query)) = url.split_once('?') else {
+        return Ok((url.to_string(), None));
+    };
+
+    let mut schema = None;
+    let mut parameters = Vec::new();
+    for parameter in query.split('&') {
+        match parameter.strip_prefix("schema=") {
+            Some(value) => schema = Some(decode_uri_component(value)?),
+            None => parameters.push(parameter),
+        }
+    }
+
+    if parameters.is_empty() {
+        Ok((base.to_string(), schema))
+    } else {
+        Ok((format!("{}?{}", base, parameters.join("&")), schema))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_and_decodes_the_schema_parameter() {
+        let split = |url: &str| split_schema_parameter(url).unwrap();
+
+        assert_eq!(
+            split("postgres://localhost/app"),
+            ("postgres://localhost/app".to_string(), None)
+        );
+        assert_eq!(
+            split("postgres://localhost/app?schema=my%20schema"),
+            (
+                "postgres://localhost/app".to_string(),
+                Some("my schema".into())
+            )
+        );
+        assert_eq!(
+            split("postgres://localhost/app?sslmode=prefer&schema=billing&user=app"),
+            (
+                "postgres://localhost/app?sslmode=prefer&user=app".to_string(),
+                Some("billing".into())
+            )
+        );
+        assert_eq!(
+            split_schema_parameter("postgres://localhost/app?schema=%zz"),
+            Err("Invalid percent-encoding in %zz".into())
+        );
+    }
+
+    #[cfg(not(feature = "postgres-tls"))]
+    #[test]
+    fn rejects_required_tls_without_the_feature() {
+        let err = PostgresBackend::connect("postgres://localhost:1/app?sslmode=require")
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(
+            err.contains("rebuild it with the postgres-tls feature"),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::HashSet;
+
+use rusqlite::Connection;
+
+use super::{Backend, SqliteUrl};
+use crate::{AnyResult, Dialect};
+
+/// The SQLite backend, for `memory`, `sqlite://` and `file:` URLs and database file paths, see
+/// [`SqliteUrl`].
+pub struct SqliteBackend {
+    connection: Connection,
+
+    /// The extensions loaded into the connection so far.
+    extensions: HashSet<String>,
+}
+
+impl SqliteBackend {
+    pub fn new(connection: Connection) -> Self {
+        Self {
+            connection,
+            extensions: HashSet::new(),
+        }
+    }
+
+    /// Opens the database of the URL, unlocked with the key if it is encrypted, and applies the
+    /// options of the URL.
+    pub fn connect(url: &SqliteUrl, key: Option<&str>) -> AnyResult<Self> {
+        let mut backend = Self::new(url.open()?);
+        if let Some(key) = key {
+            backend.apply_key(key)?;
+        }
+        url.apply_pragmas(&backend.connection)?;
+
+        Ok(backend)
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Unlocks a SQLCipher encrypted database, before any other query runs against it.
+    pub fn apply_key(&mut self, key: &str) -> AnyResult<()> {
+        Self::ensure_sqlcipher()?;
+        self.connection.pragma_update(None, "key", key)?;
+        self.check_key()
+    }
+
+    /// Changes the key of a SQLCipher encrypted database, unlocked with its current key.
+    pub fn rekey(&mut self, new_key: &str) -> AnyResult<()> {
+        Self::ensure_sqlcipher()?;
+        self.connection.pragma_update(None, "rekey", new_key)?;
+        self.check_key()
+    }
+
+    /// Plain SQLite silently ignores the key pragmas, which would leave the database unencrypted.
+    fn ensure_sqlcipher() -> AnyResult<()> {
+        if cfg!(feature = "sqlcipher") {
+            Ok(())
+        } else {
+            Err(
+                "Encrypted databases need the migrator to be built with the sqlcipher feature"
+                    .into(),
+            )
+        }
+    }
+
+    #[cfg(feature = "load-extension")]
+    fn load_extension_library(&self, name: &str) -> AnyResult<()> {
+        // SAFETY: loading an extension runs its native code, which is trusted like the migrations
+        // listing it. Loading is disabled again once the guard is dropped.
+        let result = unsafe {
+            rusqlite::LoadExtensionGuard::new(&self.connection)
+                .and_then(|_guard| self.connection.load_extension(name, None::<&str>))
+        };
+
+        result.map_err(|err| {
+            format!(
+                "{}\nCheck that it is installed and on the library search path, or give the path \
+                 of its library",
+                err
+            )
+            .into()
+        })
+    }
+
+    #[cfg(not(feature = "load-extension"))]
+    fn load_extension_library(&self, _name: &str) -> AnyResult<()> {
+        Err(
+            "Loading extensions needs the migrator to be built with the load-extension feature"
+                .into(),
+        )
+    }
+
+    /// A wrong key only shows once the database is read.
+    fn check_key(&self) -> AnyResult<()> {
+        self.connection
+            .query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))
+            .map_err(|err| format!("Cannot read the database, is the key right? {}", err).into())
+    }
+}
+
+impl Backend for SqliteBackend {
+    fn dialect(&self) -> Dialect {
+        Dialect::Sqlite
+    }
+
+    fn begin(&mut self) -> AnyResult<()> {
+        Ok(self.connection.execute_batch("BEGIN")?)
+    }
+
+    fn commit(&mut self) -> AnyResult<()> {
+        Ok(self.connection.execute_batch("COMMIT")?)
+    }
+
+    fn rollback(&mut self) -> AnyResult<()> {
+        Ok(self.connection.execute_batch("ROLLBACK")?)
+    }
+
+    fn execute_batch(&mut self, sql: &str) -> AnyResult<()> {
+        Ok(self.connection.execute_batch(sql)?)
+    }
+
+    fn create_migrations_table(&mut self) -> AnyResult<()> {
+        let conn = &self.connection;
+        conn.execute(crate::CREATE_MIGRATIONS_TABLE_SQL, [])?;
+
+        if !column_exists(conn, crate::MIGRATIONS_TABLE_NAME, "seq")? {
+            log::debug!("Upgrading migrations table");
+            conn.execute_batch(crate::UPGRADE_MIGRATIONS_TABLE_SQL)?;
+        }
+
+        if !column_exists(conn, crate::MIGRATIONS_TABLE_NAME, "kind")? {
+            log::debug!("Adding kind columns to migrations table");
+            conn.execute_batch(crate::ADD_MIGRATIONS_TABLE_KIND_COLUMNS_SQL)?;
+        }
+
+        conn.execute(crate::CREATE_MIGRATIONS_TABLE_UPDATE_TRIGGER_SQL, [])?;
+
+        Ok(())
+    }
+
+    fn current_revision(&mut self) -> AnyResult<Option<String>> {
+        let conn = &self.connection;
+        if !table_exists(conn, crate::MIGRATIONS_TABLE_NAME)? {
+            return Ok(None);
+        }
+
+        // Tables created before repeatable migrations only hold versioned rows.
+        let filter = if column_exists(conn, crate::MIGRATIONS_TABLE_NAME, "kind")? {
+            format!("WHERE kind = '{}'", crate::MIGRATION_KIND_VERSIONED)
+        } else {
+            String::new()
+        };
+
+        match conn.query_row(
+            &format!(
+                "SELECT id FROM {} {} ORDER BY rowid DESC LIMIT 1",
+                crate::MIGRATIONS_TABLE_NAME,
+                filter
+            ),
+            [],
+            |row| row.get(0),
+        ) {
+            Ok(id) => Ok(Some(id)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(err) => Err(err)?,
+        }
+    }
+
+    fn migration_records(&mut self, kind: &str) -> AnyResult<Vec<(String, Option<String>)>> {
+        let conn = &self.connection;
+        if !table_exists(conn, crate::MIGRATIONS_TABLE_NAME)?
+            || !column_exists(conn, crate::MIGRATIONS_TABLE_NAME, "kind")?
+        {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, checksum FROM {} WHERE kind = ? ORDER BY rowid",
+            crate::MIGRATIONS_TABLE_NAME
+        ))?;
+        let records = stmt
+            .query_map([kind], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(records)
+    }
+
+    fn insert_migration_record(
+        &mut self,
+        id: &str,
+        kind: &str,
+        checksum: Option<&str>,
+    ) -> AnyResult<()> {
+        self.connection.execute(
+            &format!(
+                "INSERT INTO {} (id, kind, checksum) VALUES (?, ?, ?)",
+                crate::MIGRATIONS_TABLE_NAME
+            ),
+            rusqlite::params![id, kind, checksum],
+        )?;
+
+        Ok(())
+    }
+
+    fn delete_migration_records(&mut self, id: &str, kind: &str) -> AnyResult<()> {
+        self.connection.execute(
+            &format!(
+                "DELETE FROM {} WHERE kind = ? AND id = ?",
+                crate::MIGRATIONS_TABLE_NAME
+            ),
+            [kind, id],
+        )?;
+
+        Ok(())
+    }
+
+    fn create_seeds_table(&mut self) -> AnyResult<()> {
+        self.connection.execute(crate::CREATE_SEEDS_TABLE_SQL, [])?;
+        Ok(())
+    }
+
+    fn seeded(&mut self) -> AnyResult<Vec<String>> {
+        let conn = &self.connection;
+        if !table_exists(conn, crate::SEEDS_TABLE_NAME)? {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = conn.prepare(&format!("SELECT name FROM {}", crate::SEEDS_TABLE_NAME))?;
+        let names = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(names)
+    }
+
+    fn record_seed(&mut self, name: &str, checksum: &str) -> AnyResult<()> {
+        self.connection.execute(
+            &format!(
+                "INSERT OR REPLACE INTO {} (name, checksum) VALUES (?, ?)",
+                crate::SEEDS_TABLE_NAME
+            ),
+            [name, checksum],
+        )?;
+
+        Ok(())
+    }
+
+    /// Loads the extension from the shared library `name`, a path or a library name looked up
+    /// like by `dlopen`, e.g. `mod_spatialite`.
+    fn load_extension(&mut self, name: &str) -> AnyResult<()> {
+        if self.extensions.contains(name) {
+            return Ok(());
+        }
+
+        log::debug!("Loading extension: {}", name);
+        self.load_extension_library(name)
+            .map_err(|err| format!("Cannot load SQLite extension {}: {}", name, err.to_string()))?;
+        self.extensions.insert(name.to_string());
+
+        Ok(())
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+pub fn column_exists(
+    conn: &Connection,
+    table_name: &str,
+    column_name: &str,
+) -> rusqlite::Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table_name))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(columns.iter().any(|column| column == column_name))
+}
+
+pub fn table_exists<S: AsRef<str>>(conn: &Connection, table_name: S) -> rusqlite::Result<bool> {
+    let table_name = table_name.as_ref();
+
+    let mut stmt = conn.prepare(
+        format!(
+            "SELECT name FROM sqlite_master WHERE type='table' AND name='{}'",
+            table_name
+        )
+        .as_str(),
+    )?;
+
+    let mut rows = stmt.query([])?;
+
+    Ok(rows.next().ok().flatten().is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connect(path: &std::path::Path, key: Option<&str>) -> Result<SqliteBackend, String> {
+        let url = path.to_string_lossy().parse::<SqliteUrl>()?;
+        SqliteBackend::connect(&url, key).map_err(|err| err.to_string())
+    }
+
+    #[cfg(not(feature = "sqlcipher"))]
+    #[test]
+    fn refuses_keys_without_sqlcipher() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert_eq!(
+            connect(&dir.path().join("app.db"), Some("secret")).err(),
+            Some(
+                "Encrypted databases need the migrator to be built with the sqlcipher feature"
+                    .into()
+            )
+        );
+    }
+
+    #[test]
+    fn loads_extensions_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut backend = connect(&dir.path().join("app.db"), None).unwrap();
+
+        // As if the configuration had loaded it before a migration requires it too.
+        backend.extensions.insert("mod_spatialite".to_string());
+        assert!(backend.load_extension("mod_spatialite").is_ok());
+
+        let err = backend.load_extension("mod_missing").err().unwrap();
+        assert!(
+            err.to_string()
+                .starts_with("Cannot load SQLite extension mod_missing: "),
+            "{}",
+            err.to_string()
+        );
+        assert!(!backend.extensions.contains("mod_missing"));
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn rejects_wrong_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.db");
+
+        let mut backend = connect(&path, Some("right")).unwrap();
+        assert!(
+            backend
+                .execute_batch("CREATE TABLE users (id INTEGER);")
+                .is_ok()
+        );
+        drop(backend);
+
+        let header = std::fs::read(&path).unwrap();
+        assert!(!header.starts_with(b"SQLite format 3"));
+
+        let err = connect(&path, Some("wrong")).err().unwrap();
+        assert!(
+            err.starts_with("Cannot read the database, is the key right? file is not a database"),
+            "{}",
+            err
+        );
+        assert!(connect(&path, Some("right")).is_ok());
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn rekeys_databases() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.db");
+
+        let mut backend = connect(&path, Some("old")).unwrap();
+        assert!(
+            backend
+                .execute_batch("CREATE TABLE users (id INTEGER); INSERT INTO users VALUES (1);")
+                .is_ok()
+        );
+        assert!(backend.rekey("new").is_ok());
+        drop(backend);
+
+        assert!(connect(&path, Some("old")).is_err());
+        let backend = connect(&path, Some("new")).unwrap();
+        let count: i64 = backend
+            .connection()
+            .query_row("SELECT count(*) FROM users", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+}
@@ -0,0 +1,416 @@
+use std::{path::Path, str::FromStr, time::Duration};
+
+use rusqlite::{Connection, OpenFlags};
+
+use crate::AnyResult;
+
+/// How a SQLite database is opened, from the `mode` option of its URL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqliteMode {
+    ReadOnly,
+    ReadWrite,
+    ReadWriteCreate,
+}
+
+/// A SQLite database and the options it is opened with, parsed from:
+///
+/// - `memory`, `sqlite::memory:` or `sqlite://:memory:` for an in-memory database,
+/// - `sqlite://<path>?<options>` URLs, e.g. `sqlite://data/app.db?mode=ro` or
+///   `sqlite:///var/lib/app.db`,
+/// - SQLite URI filenames, e.g. `file:data/app.db?cache=shared`,
+/// - plain paths, which take no options and are created when missing.
+///
+/// The file of a URL must exist unless `mode=rwc` is given. The options are `mode` (`ro`, `rw`
+/// or `rwc`), `cache` (`shared` or `private`), `immutable`, `busy_timeout` (in milliseconds),
+/// `journal_mode` and `foreign_keys`.
+#[derive(Debug, Clone)]
+pub struct SqliteUrl {
+    url: String,
+
+    /// The path of the database file, `None` for an in-memory database.
+    path: Option<String>,
+    mode: SqliteMode,
+    shared_cache: bool,
+    immutable: bool,
+    busy_timeout: Option<Duration>,
+    journal_mode: Option<String>,
+    foreign_keys: Option<bool>,
+}
+
+const JOURNAL_MODES: [&str; 6] = ["delete", "truncate", "persist", "memory", "wal", "off"];
+
+impl SqliteUrl {
+    pub fn as_str(&self) -> &str {
+        &self.url
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    pub fn mode(&self) -> SqliteMode {
+        self.mode
+    }
+
+    /// The database of the path, created when missing, without any other option.
+    fn new(url: &str, path: Option<String>) -> Self {
+        Self {
+            url: url.to_string(),
+            path,
+            mode: SqliteMode::ReadWriteCreate,
+            shared_cache: false,
+            immutable: false,
+            busy_timeout: None,
+            journal_mode: None,
+            foreign_keys: None,
+        }
+    }
+
+    /// Opens the database. Options set with pragmas are applied separately by
+    /// [`Self::apply_pragmas`], as the key of an encrypted database has to be given first.
+    pub fn open(&self) -> AnyResult<Connection> {
+        let mut flags = OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        flags |= match self.mode {
+            SqliteMode::ReadOnly => OpenFlags::SQLITE_OPEN_READ_ONLY,
+            SqliteMode::ReadWrite => OpenFlags::SQLITE_OPEN_READ_WRITE,
+            SqliteMode::ReadWriteCreate => {
+                OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
+            }
+        };
+        if self.shared_cache {
+            flags |= OpenFlags::SQLITE_OPEN_SHARED_CACHE;
+        }
+
+        let Some(path) = &self.path else {
+            return Ok(Connection::open_with_flags("file::memory:", flags)?);
+        };
+
+        if self.mode != SqliteMode::ReadWriteCreate && !Path::new(path).exists() {
+            return Err(format!(
+                "Database file {} does not exist, add mode=rwc to the URL to create it",
+                path
+            )
+            .into());
+        }
+
+        let mut uri = format!("file:{}", encode_uri_path(path));
+        if self.immutable {
+            uri.push_str("?immutable=1");
+        }
+
+        Connection::open_with_flags(&uri, flags)
+            .map_err(|err| format!("Cannot open database file {}: {}", path, err).into())
+    }
+
+    /// Applies the options of the URL set with pragmas.
+    pub fn apply_pragmas(&self, connection: &Connection) -> AnyResult<()> {
+        if let Some(timeout) = self.busy_timeout {
+            connection.busy_timeout(timeout)?;
+        }
+
+        if let Some(journal_mode) = &self.journal_mode {
+            let applied =
+                connection.pragma_update_and_check(None, "journal_mode", journal_mode, |row| {
+                    row.get::<_, String>(0)
+                })?;
+            if !applied.eq_ignore_ascii_case(journal_mode) {
+                log::warn!(
+                    "The database kept the {} journal mode instead of {}",
+                    applied,
+                    journal_mode
+                );
+            }
+        }
+
+        if let Some(foreign_keys) = self.foreign_keys {
+            connection.pragma_update(None, "foreign_keys", foreign_keys)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_option(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("Invalid value {} of the {} option", value, key);
+
+        match key {
+            "mode" => {
+                self.mode = match value {
+                    "ro" => SqliteMode::ReadOnly,
+                    "rw" => SqliteMode::ReadWrite,
+                    "rwc" => SqliteMode::ReadWriteCreate,
+                    _ => return Err(invalid()),
+                }
+            }
+            "cache" => {
+                self.shared_cache = match value {
+                    "shared" => true,
+                    "private" => false,
+                    _ => return Err(invalid()),
+                }
+            }
+            "immutable" => self.immutable = parse_bool(value).ok_or_else(invalid)?,
+            "busy_timeout" => {
+                let millis = value.parse::<u64>().map_err(|_| invalid())?;
+                self.busy_timeout = Some(Duration::from_millis(millis));
+            }
+            "journal_mode" => {
+                let journal_mode = value.to_lowercase();
+                if !JOURNAL_MODES.contains(&journal_mode.as_str()) {
+                    return Err(invalid());
+                }
+                self.journal_mode = Some(journal_mode);
+            }
+            "foreign_keys" => self.foreign_keys = Some(parse_bool(value).ok_or_else(invalid)?),
+            _ => return Err(format!("Unknown option {} of SQLite database URLs", key)),
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for SqliteUrl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if matches!(s, "memory" | "sqlite::memory:") {
+            return Ok(Self::new(s, None));
+        }
+
+        let rest = if let Some(rest) = s.strip_prefix("sqlite://") {
+            rest
+        } else if let Some(rest) = s.strip_prefix("file:") {
+            // `file://host/path` URIs may only name the local host.
+            match rest.strip_prefix("//") {
+                Some(authority_and_path) => {
+                    let path_start = authority_and_path
+                        .find('/')
+                        .unwrap_or(authority_and_path.len());
+                    match &authority_and_path[..path_start] {
+                        "" | "localhost" => &authority_and_path[path_start..],
+                        host => return Err(format!("Unsupported host {} in {}", host, s)),
+                    }
+                }
+                None => rest,
+            }
+        } else {
+            return Ok(Self::new(s, Some(s.to_string())));
+        };
+
+        let rest = rest.split('#').next().unwrap_or_default();
+        let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let path = decode_uri_component(path)?;
+        if path.is_empty() {
+            return Err(format!("The database URL names no file: {}", s));
+        }
+
+        let mut url = if path == ":memory:" {
+            Self::new(s, None)
+        } else {
+            let mut url = Self::new(s, Some(path));
+            url.mode = SqliteMode::ReadWrite;
+            url
+        };
+
+        for option in query.split('&').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            url.set_option(key, &decode_uri_component(value)?)?;
+        }
+
+        if url.path.is_none() && (url.mode != SqliteMode::ReadWriteCreate || url.immutable) {
+            return Err(format!(
+                "The mode and immutable options do not apply to in-memory databases: {}",
+                s
+            ));
+        }
+
+        Ok(url)
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "1" | "true" | "on" | "yes" => Some(true),
+        "0" | "false" | "off" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+pub(crate) fn decode_uri_component(s: &str) -> Result<String, String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail
+                .get(..2)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| format!("Invalid percent-encoding in {}", s))?;
+            bytes.push(hex);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+
+    String::from_utf8(bytes).map_err(|_| format!("Invalid percent-encoding in {}", s))
+}
+
+/// Escapes the characters that would end the path of a `file:` URI filename.
+fn encode_uri_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for c in path.chars() {
+        match c {
+            '%' | '?' | '#' => encoded.push_str(&format!("%{:02X}", c as u32)),
+            _ => encoded.push(c),
+        }
+    }
+
+    // A relative path starting with `//` would be read as an authority.
+    if encoded.starts_with("//") {
+        encoded.insert_str(0, "//");
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<SqliteUrl, String> {
+        s.parse()
+    }
+
+    #[test]
+    fn parses_in_memory_databases() {
+        for s in ["memory", "sqlite::memory:", "sqlite://:memory:"] {
+            let url = parse(s).unwrap();
+            assert_eq!(url.path(), None, "{}", s);
+            assert_eq!(url.mode(), SqliteMode::ReadWriteCreate, "{}", s);
+            assert_eq!(url.as_str(), s);
+        }
+
+        assert!(parse("sqlite://:memory:?cache=shared&mode=rwc").is_ok());
+        for s in ["sqlite://:memory:?mode=ro", "sqlite://:memory:?immutable=1"] {
+            assert_eq!(
+                parse(s).unwrap_err(),
+                format!(
+                    "The mode and immutable options do not apply to in-memory databases: {}",
+                    s
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn parses_paths() {
+        let url = parse("sqlite:///abs/path.db").unwrap();
+        assert_eq!(url.path(), Some("/abs/path.db"));
+        assert_eq!(url.mode(), SqliteMode::ReadWrite);
+
+        let url = parse("sqlite://data/app.db?mode=ro").unwrap();
+        assert_eq!(url.path(), Some("data/app.db"));
+        assert_eq!(url.mode(), SqliteMode::ReadOnly);
+
+        let url = parse("file:data/app.db?mode=rwc#fragment").unwrap();
+        assert_eq!(url.path(), Some("data/app.db"));
+        assert_eq!(url.mode(), SqliteMode::ReadWriteCreate);
+
+        // Plain paths take no options, and are created when missing.
+        let url = parse("data/app.db?mode=ro").unwrap();
+        assert_eq!(url.path(), Some("data/app.db?mode=ro"));
+        assert_eq!(url.mode(), SqliteMode::ReadWriteCreate);
+
+        assert_eq!(
+            parse("sqlite://?mode=ro").unwrap_err(),
+            "The database URL names no file: sqlite://?mode=ro"
+        );
+    }
+
+    #[test]
+    fn accepts_only_the_local_host_in_file_uris() {
+        assert_eq!(
+            parse("file://localhost/var/app.db").unwrap().path(),
+            Some("/var/app.db")
+        );
+        assert_eq!(
+            parse("file:///var/app.db").unwrap().path(),
+            Some("/var/app.db")
+        );
+        assert_eq!(
+            parse("file://otherhost/var/app.db").unwrap_err(),
+            "Unsupported host otherhost in file://otherhost/var/app.db"
+        );
+    }
+
+    #[test]
+    fn decodes_percent_encoding() {
+        let url = parse("sqlite://my%20data/app%3F.db?journal_mode=%57AL").unwrap();
+        assert_eq!(url.path(), Some("my data/app?.db"));
+        assert_eq!(url.journal_mode.as_deref(), Some("wal"));
+
+        assert_eq!(
+            parse("sqlite://app%zz.db").unwrap_err(),
+            "Invalid percent-encoding in app%zz.db"
+        );
+        assert_eq!(
+            parse("sqlite://app%2.db").unwrap_err(),
+            "Invalid percent-encoding in app%2.db"
+        );
+        assert_eq!(
+            parse("sqlite://app.db?journal_mode=%ff").unwrap_err(),
+            "Invalid percent-encoding in %ff"
+        );
+    }
+
+    #[test]
+    fn validates_options() {
+        let url =
+            parse("sqlite://app.db?cache=shared&immutable=yes&busy_timeout=250&foreign_keys=off")
+                .unwrap();
+        assert!(url.shared_cache);
+        assert!(url.immutable);
+        assert_eq!(url.busy_timeout, Some(Duration::from_millis(250)));
+        assert_eq!(url.foreign_keys, Some(false));
+
+        assert_eq!(
+            parse("sqlite://app.db?timeout=5").unwrap_err(),
+            "Unknown option timeout of SQLite database URLs"
+        );
+        assert_eq!(
+            parse("sqlite://app.db?mode=rwx").unwrap_err(),
+            "Invalid value rwx of the mode option"
+        );
+        assert_eq!(
+            parse("sqlite://app.db?journal_mode=fast").unwrap_err(),
+            "Invalid value fast of the journal_mode option"
+        );
+    }
+
+    #[test]
+    fn encodes_the_characters_ending_uri_paths() {
+        assert_eq!(encode_uri_path("data/app.db"), "data/app.db");
+        assert_eq!(encode_uri_path("a?b#c%d.db"), "a%3Fb%23c%25d.db");
+        assert_eq!(encode_uri_path("//server/app.db"), "////server/app.db");
+    }
+
+    #[test]
+    fn opens_files_whose_names_need_encoding() {
+        let dir = tempfile::tempdir().unwrap();
+
+        for name in ["what?.db", "#1.db", "100%.db"] {
+            let path = dir.path().join(name).to_string_lossy().to_string();
+            let url = SqliteUrl::from_str(&path).unwrap();
+
+            url.open()
+                .map_err(|err| err.to_string())
+                .unwrap()
+                .execute_batch("CREATE TABLE t (id INTEGER);")
+                .unwrap();
+            assert!(Path::new(&path).exists(), "{}", path);
+        }
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+}
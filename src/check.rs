@@ -0,0 +1,456 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use crate::{
+    AnyResult, Dialect, MigrationId, MigrationKind, Migrator, migrations::split_migration_stem,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CheckIssueKind {
+    UnparsableFilename,
+    DuplicateId,
+    MissingDown,
+    MissingUp,
+    InvalidHistory,
+    MultipleHeads,
+    TableConflict,
+}
+
+impl CheckIssueKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckIssueKind::UnparsableFilename => "unparsable-filename",
+            CheckIssueKind::DuplicateId => "duplicate-id",
+            CheckIssueKind::MissingDown => "missing-down",
+            CheckIssueKind::MissingUp => "missing-up",
+            CheckIssueKind::InvalidHistory => "invalid-history",
+            CheckIssueKind::MultipleHeads => "multiple-heads",
+            CheckIssueKind::TableConflict => "table-conflict",
+        }
+    }
+}
+
+/// A problem found in the migration set by [`check_migrations`].
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CheckIssue {
+    pub kind: CheckIssueKind,
+
+    /// The file the issue is about, if it can be attributed to a single file.
+    pub file: Option<String>,
+
+    pub message: String,
+}
+
+impl CheckIssue {
+    fn new(kind: CheckIssueKind, file: Option<String>, message: String) -> Self {
+        Self {
+            kind,
+            file,
+            message,
+        }
+    }
+}
+
+/// Which migrations are checked against each other for touching the same table.
+#[derive(Debug, Clone, Copy)]
+pub enum PendingMigrations<'a> {
+    /// Migrations created on parallel branches, i.e. neither depends on the other, whether or
+    /// not the branches have been merged since.
+    Parallel,
+
+    /// Migrations not yet applied on top of the given current migration of a database, checked
+    /// against each other and against the applied migrations of parallel branches.
+    Unapplied(Option<&'a MigrationId>),
+}
+
+/// Validates the migrations directory, collecting every problem instead of stopping at the first.
+pub fn check_migrations(
+    migrator: &Migrator,
+    dialect: Dialect,
+    pending: PendingMigrations<'_>,
+) -> AnyResult<Vec<CheckIssue>> {
+    let mut issues = Vec::new();
+
+    let up_dir = dialect.up_dir();
+    let down_dir = dialect.down_dir();
+    let up_stems = check_migration_dir(&up_dir, &mut issues)?;
+    let down_stems = check_migration_dir(&down_dir, &mut issues)?;
+
+    for stem in up_stems.difference(&down_stems) {
+        issues.push(CheckIssue::new(
+            CheckIssueKind::MissingDown,
+            Some(format!("{}/{}.sql", up_dir, stem)),
+            format!("Migration {} is missing its down file", stem),
+        ));
+    }
+
+    for stem in down_stems.difference(&up_stems) {
+        issues.push(CheckIssue::new(
+            CheckIssueKind::MissingUp,
+            Some(format!("{}/{}.sql", down_dir, stem)),
+            format!("Migration {} is missing its up file", stem),
+        ));
+    }
+
+    let mut stems_by_id = BTreeMap::<MigrationId, Vec<&String>>::new();
+    for stem in up_stems.union(&down_stems) {
+        if let Some((id, _)) = split_migration_stem(stem) {
+            stems_by_id.entry(id).or_default().push(stem);
+        }
+    }
+
+    for (id, stems) in stems_by_id.iter().filter(|(_, stems)| stems.len() > 1) {
+        let filenames = stems.iter().map(|stem| format!("{}.sql", stem));
+        issues.push(CheckIssue::new(
+            CheckIssueKind::DuplicateId,
+            None,
+            format!(
+                "Migration id {} is used by multiple migrations: {}",
+                id,
+                filenames.collect::<Vec<_>>().join(", ")
+            ),
+        ));
+    }
+
+    // The graph can only be built from a consistent set of files.
+    if !issues.is_empty() {
+        return Ok(issues);
+    }
+
+    let graph = match migrator.history(dialect) {
+        Ok(graph) => graph,
+        Err(err) => {
+            issues.push(CheckIssue::new(
+                CheckIssueKind::InvalidHistory,
+                None,
+                err.to_string().trim_end().to_string(),
+            ));
+            return Ok(issues);
+        }
+    };
+
+    let heads = graph.heads();
+    if heads.len() > 1 {
+        let heads = heads
+            .iter()
+            .map(|head| graph.migrations()[*head].generate_filename())
+            .collect::<Vec<_>>();
+        issues.push(CheckIssue::new(
+            CheckIssueKind::MultipleHeads,
+            None,
+            format!(
+                "Multiple heads found, merge them with 'migrate merge': {}",
+                heads.join(", ")
+            ),
+        ));
+    }
+
+    let ancestors = (0..graph.len())
+        .map(|index| graph.ancestors(index))
+        .collect::<Vec<_>>();
+
+    let applied = match pending {
+        PendingMigrations::Unapplied(Some(current)) => graph
+            .index_of(current)
+            .map(|index| ancestors[index].clone())
+            .ok_or("Database has invalid current migration")?,
+        _ => HashSet::new(),
+    };
+    let unapplied = |index: &usize| !applied.contains(index);
+
+    let tables = (0..graph.len())
+        .filter(|index| graph.migrations()[*index].kind() == MigrationKind::Sql)
+        .map(|index| {
+            let sql = std::fs::read_to_string(graph.migrations()[index].up_path())?;
+            Ok((index, touched_tables(&sql)))
+        })
+        .collect::<AnyResult<Vec<_>>>()?;
+
+    for (position, (first, first_tables)) in tables.iter().enumerate() {
+        for (second, second_tables) in tables.iter().skip(position + 1) {
+            // A merge does not resolve a conflict, so parallel migrations are compared even once
+            // a migration depends on both.
+            let parallel =
+                !ancestors[*first].contains(second) && !ancestors[*second].contains(first);
+            let compared = match pending {
+                PendingMigrations::Parallel => parallel,
+                PendingMigrations::Unapplied(_) => {
+                    (unapplied(first) && unapplied(second))
+                        || (parallel && (unapplied(first) || unapplied(second)))
+                }
+            };
+            if !compared {
+                continue;
+            }
+
+            let shared = first_tables
+                .intersection(second_tables)
+                .cloned()
+                .collect::<Vec<_>>();
+            if shared.is_empty() {
+                continue;
+            }
+
+            let first = &graph.migrations()[*first];
+            let second = &graph.migrations()[*second];
+            issues.push(CheckIssue::new(
+                CheckIssueKind::TableConflict,
+                Some(second.up_path()),
+                format!(
+                    "Migrations {} and {} both touch the following tables: {}",
+                    first.stringify_id(),
+                    second.stringify_id(),
+                    shared.join(", ")
+                ),
+            ));
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Returns the names (without extension) of the well-formed migration files in `dir`, reporting
+/// the others as unparsable.
+fn check_migration_dir(dir: &str, issues: &mut Vec<CheckIssue>) -> AnyResult<BTreeSet<String>> {
+    let mut stems = BTreeSet::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let filename = entry?.file_name().to_string_lossy().to_string();
+        let stem = filename.strip_suffix(".sql");
+
+        match stem.filter(|stem| split_migration_stem(stem).is_some()) {
+            Some(stem) => {
+                stems.insert(stem.to_string());
+            }
+            None => issues.push(CheckIssue::new(
+                CheckIssueKind::UnparsableFilename,
+                Some(format!("{}/{}", dir, filename)),
+                format!(
+                    "Cannot parse migration filename {}, expected <id>_<name>.sql",
+                    filename
+                ),
+            )),
+        }
+    }
+
+    Ok(stems)
+}
+
+/// Collects the names of the tables the SQL creates, alters, drops, or modifies rows of.
+pub fn touched_tables(sql: &str) -> BTreeSet<String> {
+    let tokens = tokenize(sql);
+    let mut tables = BTreeSet::new();
+
+    let word = |index: usize| tokens.get(index).map(String::as_str).unwrap_or_default();
+    let skip_words = |index: usize, words: &[&str]| {
+        let matches = words
+            .iter()
+            .enumerate()
+            .all(|(offset, w)| word(index + offset) == *w);
+
+        if matches { index + words.len() } else { index }
+    };
+
+    for (index, token) in tokens.iter().enumerate() {
+        let name_index = match (token.as_str(), word(index + 1)) {
+            ("create", _) => {
+                let mut next = index + 1;
+                next = skip_words(next, &["temp"]);
+                next = skip_words(next, &["temporary"]);
+                next = skip_words(next, &["unique"]);
+
+                match word(next) {
+                    "table" => Some(skip_words(next + 1, &["if", "not", "exists"])),
+                    "index" => {
+                        let next = skip_words(next + 1, &["if", "not", "exists"]);
+                        let next = skip_qualified_name(&tokens, next);
+                        (word(next) == "on").then_some(next + 1)
+                    }
+                    _ => None,
+                }
+            }
+            ("alter", "table") => Some(index + 2),
+            ("drop", "table") => Some(skip_words(index + 2, &["if", "exists"])),
+            ("insert" | "replace", "into") => Some(index + 2),
+            ("insert", "or") if word(index + 3) == "into" => Some(index + 4),
+            ("delete", "from") => Some(index + 2),
+            ("update", "or") => Some(index + 3),
+            ("update", _)
+                if !matches!(
+                    word(index.wrapping_sub(1)),
+                    "on" | "before" | "after" | "of"
+                ) =>
+            {
+                Some(index + 1)
+            }
+            _ => None,
+        };
+
+        if let Some(name) = name_index.and_then(|index| qualified_name(&tokens, index)) {
+            tables.insert(name);
+        }
+    }
+
+    tables
+}
+
+/// Returns the table name at `index`, dropping the schema of `schema.table` names.
+fn qualified_name(tokens: &[String], index: usize) -> Option<String> {
+    let end = skip_qualified_name(tokens, index);
+    let name = tokens.get(end.checked_sub(1)?)?;
+
+    let is_identifier = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphanumeric() || c == '_');
+    (end > index && is_identifier).then(|| name.clone())
+}
+
+fn skip_qualified_name(tokens: &[String], mut index: usize) -> usize {
+    if index < tokens.len() {
+        index += 1;
+    }
+
+    while tokens.get(index).is_some_and(|t| t == ".") && index + 1 < tokens.len() {
+        index += 2;
+    }
+
+    index
+}
+
+/// Splits SQL into lowercase words and punctuation, unquoting identifiers and dropping comments
+/// and string literals.
+fn tokenize(sql: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = sql.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '-' if chars.peek() == Some(&'-') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = '\0';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            '\'' => {
+                for c in chars.by_ref() {
+                    if c == '\'' {
+                        break;
+                    }
+                }
+                tokens.push("'".to_string());
+            }
+            '"' | '`' | '[' => {
+                let closing = if c == '[' { ']' } else { c };
+                let identifier = chars
+                    .by_ref()
+                    .take_while(|c| *c != closing)
+                    .collect::<String>();
+                tokens.push(identifier.to_lowercase());
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_' || *c == '$')
+                {
+                    word.push(c);
+                }
+                tokens.push(word.to_lowercase());
+            }
+            c if c.is_whitespace() => {}
+            c => tokens.push(c.to_string()),
+        }
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tables(sql: &str) -> Vec<String> {
+        touched_tables(sql).into_iter().collect()
+    }
+
+    #[test]
+    fn tokenizes_quoted_identifiers_and_drops_comments_and_strings() {
+        assert_eq!(
+            tokenize(
+                "SELECT \"My Col\", `b`, [c] -- create table x\nFROM t /* drop table y */ WHERE a = 'drop table z'"
+            ),
+            [
+                "select", "my col", ",", "b", ",", "c", "from", "t", "where", "a", "=", "'"
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_created_altered_and_dropped_tables() {
+        assert_eq!(
+            tables(
+                "CREATE TABLE users (id INTEGER);
+                CREATE TEMP TABLE IF NOT EXISTS scratch (id INTEGER);
+                ALTER TABLE posts ADD COLUMN title TEXT;
+                DROP TABLE IF EXISTS comments;
+                DROP INDEX idx_ignored;"
+            ),
+            ["comments", "posts", "scratch", "users"]
+        );
+    }
+
+    #[test]
+    fn finds_indexed_and_modified_tables() {
+        assert_eq!(
+            tables(
+                "CREATE UNIQUE INDEX IF NOT EXISTS idx_users_email ON users (email);
+                INSERT INTO roles (name) VALUES ('admin');
+                INSERT OR IGNORE INTO settings VALUES (1);
+                REPLACE INTO caches VALUES (1);
+                DELETE FROM sessions;
+                UPDATE OR ABORT accounts SET a = 1;
+                UPDATE profiles SET b = 2;"
+            ),
+            [
+                "accounts", "caches", "profiles", "roles", "sessions", "settings", "users"
+            ]
+        );
+    }
+
+    #[test]
+    fn unquotes_identifiers_and_drops_schemas() {
+        assert_eq!(
+            tables(
+                "CREATE TABLE \"Order Items\" (id INTEGER);
+                ALTER TABLE `app`.`Invoices` ADD COLUMN total INTEGER;
+                INSERT INTO main.[line items] VALUES (1);
+                DROP TABLE public.\"Old\";
+                CREATE INDEX idx_a ON app.accounts (id);"
+            ),
+            ["accounts", "invoices", "line items", "old", "order items"]
+        );
+    }
+
+    #[test]
+    fn ignores_tables_named_in_comments_strings_and_triggers() {
+        assert_eq!(
+            tables(
+                "-- DROP TABLE commented;
+                /* ALTER TABLE blocked ADD x INTEGER; */
+                INSERT INTO logs (message) VALUES ('DELETE FROM quoted');
+                CREATE TRIGGER t AFTER UPDATE ON watched BEGIN SELECT 1; END;"
+            ),
+            ["logs"]
+        );
+    }
+}
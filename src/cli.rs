@@ -1,13 +1,80 @@
-use crate::Revision;
-use clap::{Parser, Subcommand};
+use crate::{
+    AnyResult, Backend, Dialect, MysqlBackend, PostgresBackend, Revision, SqliteBackend, SqliteUrl,
+};
+use clap::{Parser, Subcommand, ValueEnum};
 use constcat::concat;
 use std::str::FromStr;
 
 const ABOUT: &str = "A simple database migration tool";
 const MIGRATE_DESC: &str = "Commands related to create, apply and revert migrations";
 const HISTORY_DESC: &str = "Show the history of the migrations in the <MIGRATIONS_DIR> directory";
+const STATUS_DESC: &str = "Show which migrations are applied to the database and which are pending";
+const REKEY_DESC: &str = "Change the key of a SQLite database encrypted with SQLCipher";
+const REKEY_DESC_LONG: &str = "Change the key of a SQLite database encrypted with SQLCipher.
+
+The current key is given like for every other command, with --key-file, the MIGRATOR_DB_KEY
+environment variable or the key_file of the [sqlite] section of the configuration. The new key
+is read from --new-key-file or the MIGRATOR_DB_NEW_KEY environment variable. Encrypted databases
+need the migrator to be built with the sqlcipher feature.";
+const SEED_DESC: &str = "Load the seed data of the <SEEDS_DIR> directory into the database";
+const SEED_DESC_LONG: &str = "Load the seed data of the <SEEDS_DIR> directory into the database.
+
+Seeds run once per database, after every migration has been applied. A seed restricted to some
+environments with a '-- migrator:env dev, test' header only runs when one of them is given with
+--env. Use --force to run seeds that already ran again, e.g. to reset development fixtures.";
 
 const MIGRATE_CREATE_DESC: &str = "Create a new migration with <name> and current date and time in the following format: <YYYYMMDDHHMMSS>_<name>.sql";
+const MIGRATE_CREATE_DESC_LONG: &str = "Create a new migration with <name> and current date and time in the following format: <YYYYMMDDHHMMSS>_<name>.sql.
+
+The id scheme can be changed in the configuration file to millisecond timestamps
+(YYYYMMDDHHMMSSmmm) or zero-padded sequential numbers (0001). New ids always sort after the
+existing migrations, even when several migrations are created within the same second.
+
+With --template, the up and down files are filled from a template, whose {{placeholders}} take
+the <KEY=VALUE> arguments:
+
+  migrate create add_email --template add-column table=users column=email type=TEXT
+
+Built-in templates:
+- create-table: table
+- add-column: table, column, type (default TEXT)
+- add-index: table, column, index (default <table>_<column>_idx)
+- rebuild-table: table, a SQLite table rebuild to change column types or constraints, failing
+  until the new and previous definitions of the table are written
+
+Project templates are directories of <TEMPLATES_DIR> holding an up.sql and a down.sql file.";
+const MIGRATE_MERGE_DESC: &str =
+    "Create an empty migration depending on every head, merging the branches of the history";
+const MIGRATE_SHOW_DESC: &str = "Print the metadata and the up and down SQL of the migration";
+const MIGRATE_EDIT_DESC: &str = "Open the up and down files of the migration in $VISUAL or $EDITOR";
+const MIGRATE_RENAME_DESC: &str = "Rename the up and down files of the migration, keeping its id";
+const MIGRATE_REMOVE_DESC: &str = "Delete the up and down files of a migration not applied yet";
+const MIGRATE_CHECK_DESC: &str = "Check the migrations for problems, failing if any is found";
+const MIGRATE_CHECK_DESC_LONG: &str = "Check the migrations for problems, failing if any is found.
+
+The following problems are reported:
+- unparsable migration filenames
+- migration ids used by more than one migration
+- migrations missing their up or down file
+- invalid depends-on headers and circular dependencies
+- multiple heads
+- two migrations touching the same table, either on parallel branches not merged yet or, if
+  <DATABASE_URL> is given, among the migrations not yet applied to the database";
+const MIGRATE_TEST_DESC: &str =
+    "Apply every migration to a scratch database and run the assertions of the tests directory";
+const MIGRATE_TEST_DESC_LONG: &str =
+    "Apply every migration to a scratch database and run the assertions of the tests directory.
+
+The migrations are applied up to head to an in-memory SQLite database, or a temporary file with
+--temp-file, which is thrown away afterwards. With --roundtrip, they are then reverted to base and
+applied again, failing if the down migrations do not restore the empty schema or if applying
+them again gives another schema.
+
+Every .sql file of migrations/<dialect>/tests is then run as an assertion, each against the
+database as the migrations left it. An assertion fails if one of its statements fails, or if a
+query returns a row whose first column is NULL, zero or empty, e.g.
+
+    SELECT COUNT(*) = 1 FROM pragma_table_info('users') WHERE name = 'email';";
 const MIGRATE_UP_DESC_SHORT: &str = "Apply the migration with the given <revision> to the database";
 const MIGRATE_DOWN_DESC_SHORT: &str =
     "Revert the migration with the given <revision> from the database";
@@ -28,6 +95,9 @@ const MIGRATE_DOWN_DESC_LONG: &str = concat!(
 
 const REVISION_HELP: &str = "The <revision> can be the following:
 - An absolute revision ID (e.g. 20210101103015)
+- A unique prefix of a revision ID (e.g. 20210101)
+- The name of a migration (e.g. add_users_table)
+- A date, selecting the last migration created on or before it (e.g. @2021-01-01)
 - A relative revision offset in respect to the latest (head) revision:
   - head - the latest revision
   - head:0 - the same as 'head'
@@ -36,7 +106,14 @@ const REVISION_HELP: &str = "The <revision> can be the following:
   - current - the current migration state
   - current:0 - the same as 'current'
   - current:1 - the next revision
-  - current:-1 - the previous revision";
+  - current:-1 - the previous revision
+  - +1 - the same as 'current:1'
+  - -1 - the same as 'current:-1'
+- base - the state before the first migration
+
+Migrations form a graph through their '-- migrator:depends-on <id>' headers. Offsets walk the
+graph: backwards through the first parent of merge migrations, and forwards only as long as
+the path does not branch.";
 
 #[derive(Parser)]
 #[clap(name = "migrator")]
@@ -45,6 +122,16 @@ const REVISION_HELP: &str = "The <revision> can be the following:
 pub struct Cli {
     #[clap(subcommand)]
     pub command: Commands,
+
+    /// The dialect of the migrations to work with when no database is given, overriding the
+    /// `dialect` of the configuration file
+    #[clap(long, global = true, value_enum)]
+    pub dialect: Option<Dialect>,
+
+    /// The file holding the key of SQLite databases encrypted with SQLCipher, overriding the
+    /// MIGRATOR_DB_KEY environment variable and the configuration file
+    #[clap(long, global = true)]
+    pub key_file: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -59,6 +146,52 @@ pub enum Commands {
         /// If provided, the migration state of the database will be marked as current
         #[clap(short, long)]
         database_url: Option<DatabaseUrl>,
+
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+
+    #[clap(name = "status")]
+    #[clap(about = STATUS_DESC)]
+    Status {
+        #[clap(short, long)]
+        database_url: DatabaseUrl,
+
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+
+    #[clap(name = "seed")]
+    #[clap(about = SEED_DESC, long_about = SEED_DESC_LONG)]
+    Seed {
+        /// The seeds to run, every seed if none is given
+        names: Vec<String>,
+
+        #[clap(short, long)]
+        database_url: DatabaseUrl,
+
+        /// The environment seeds are run for (e.g. dev, test, prod)
+        #[clap(short, long, env = "MIGRATOR_ENV")]
+        env: Option<String>,
+
+        /// Run seeds again even if they already ran against the database
+        #[clap(long)]
+        force: bool,
+
+        /// Set a variable substituted for the ${KEY} placeholders of the seeds
+        #[clap(long = "var", value_name = "KEY=VALUE", value_parser = crate::parse_variable)]
+        vars: Vec<(String, String)>,
+    },
+
+    #[clap(name = "rekey")]
+    #[clap(about = REKEY_DESC, long_about = REKEY_DESC_LONG)]
+    Rekey {
+        #[clap(short, long)]
+        database_url: DatabaseUrl,
+
+        /// The file holding the new key
+        #[clap(long)]
+        new_key_file: Option<String>,
     },
 }
 
@@ -71,46 +204,237 @@ pub struct Migrate {
 #[derive(Subcommand)]
 pub enum MigrateCommands {
     #[clap(name = "create")]
-    #[clap(about = MIGRATE_CREATE_DESC)]
-    Create { name: String },
+    #[clap(about = MIGRATE_CREATE_DESC, long_about = MIGRATE_CREATE_DESC_LONG)]
+    Create {
+        name: String,
+
+        /// The template the migration is created from
+        #[clap(short, long)]
+        template: Option<String>,
+
+        /// The values of the placeholders of the template
+        #[clap(value_name = "KEY=VALUE", value_parser = crate::parse_variable, requires = "template")]
+        values: Vec<(String, String)>,
+    },
+
+    #[clap(name = "merge")]
+    #[clap(about = MIGRATE_MERGE_DESC)]
+    Merge {
+        /// The name of the merge migration
+        #[clap(default_value = crate::MIGRATION_MERGE_DEFAULT_NAME)]
+        name: String,
+    },
+
+    #[clap(name = "show")]
+    #[clap(about = MIGRATE_SHOW_DESC)]
+    Show { revision: Revision },
+
+    #[clap(name = "edit")]
+    #[clap(about = MIGRATE_EDIT_DESC)]
+    Edit { revision: Revision },
+
+    #[clap(name = "rename")]
+    #[clap(about = MIGRATE_RENAME_DESC)]
+    Rename {
+        revision: Revision,
+        new_name: String,
+
+        /// If provided, migrations applied to the database are only renamed with --force
+        #[clap(short, long)]
+        database_url: Option<DatabaseUrl>,
+
+        /// Rename the migration even if it is applied to the database
+        #[clap(long)]
+        force: bool,
+    },
+
+    #[clap(name = "remove")]
+    #[clap(about = MIGRATE_REMOVE_DESC)]
+    Remove {
+        revision: Revision,
+
+        /// A database the migration must not be applied to, can be given several times
+        #[clap(short, long)]
+        database_url: Vec<DatabaseUrl>,
+
+        /// Remove the migration even if other migrations depend on it. They are made to depend on
+        /// its parents instead
+        #[clap(long)]
+        force: bool,
+    },
+
+    #[clap(name = "check")]
+    #[clap(about = MIGRATE_CHECK_DESC, long_about = MIGRATE_CHECK_DESC_LONG)]
+    Check {
+        /// If provided, the migrations not yet applied to the database are checked for conflicts
+        #[clap(short, long)]
+        database_url: Option<DatabaseUrl>,
+
+        #[clap(short, long, value_enum, default_value_t = CheckFormat::Text)]
+        format: CheckFormat,
+    },
+
+    #[clap(name = "test")]
+    #[clap(about = MIGRATE_TEST_DESC, long_about = MIGRATE_TEST_DESC_LONG)]
+    Test {
+        /// Revert every migration and apply them again before running the assertions
+        #[clap(long)]
+        roundtrip: bool,
+
+        /// Use a temporary database file instead of an in-memory database
+        #[clap(long)]
+        temp_file: bool,
+
+        /// The environment migrations are applied in. Migrations restricted to other environments
+        /// are skipped
+        #[clap(short, long, env = "MIGRATOR_ENV")]
+        env: Option<String>,
+
+        /// Set a variable substituted for the ${KEY} placeholders of the migrations
+        #[clap(long = "var", value_name = "KEY=VALUE", value_parser = crate::parse_variable)]
+        vars: Vec<(String, String)>,
+    },
 
     #[clap(name = "up")]
     #[clap(about = MIGRATE_UP_DESC_SHORT, long_about = MIGRATE_UP_DESC_LONG)]
     Up {
-        revision: Revision,
+        #[clap(required_unless_present = "steps", allow_negative_numbers = true)]
+        revision: Option<Revision>,
 
-        #[clap(short, long)]
-        database_url: DatabaseUrl,
+        /// The number of migrations to apply, instead of a revision
+        #[clap(long, conflicts_with = "revision", value_parser = clap::value_parser!(i32).range(1..))]
+        steps: Option<i32>,
+
+        #[clap(
+            short,
+            long,
+            required_unless_present_any = ["database_glob", "database_list"],
+            conflicts_with_all = ["database_glob", "database_list"]
+        )]
+        database_url: Option<DatabaseUrl>,
+
+        /// Migrate every SQLite database file matching the pattern, e.g. 'tenants/*.db'
+        #[clap(long, value_name = "PATTERN", conflicts_with_all = ["database_list", "dry_run"])]
+        database_glob: Option<String>,
+
+        /// Migrate every database of the file, one URL or path per line
+        #[clap(long, value_name = "FILE", conflicts_with = "dry_run")]
+        database_list: Option<String>,
+
+        /// How many databases of --database-glob or --database-list are migrated at once,
+        /// defaulting to the number of CPUs
+        #[clap(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+        jobs: Option<u32>,
+
+        /// Keep migrating the other databases when one fails, instead of stopping
+        #[clap(long)]
+        keep_going: bool,
+
+        /// The environment migrations are applied in. Migrations restricted to other environments
+        /// are skipped
+        #[clap(short, long, env = "MIGRATOR_ENV")]
+        env: Option<String>,
+
+        /// Set a variable substituted for the ${KEY} placeholders of the migrations
+        #[clap(long = "var", value_name = "KEY=VALUE", value_parser = crate::parse_variable)]
+        vars: Vec<(String, String)>,
+
+        /// Only print the migrations that would be applied, without touching the database
+        #[clap(long)]
+        dry_run: bool,
+
+        /// The output format of the dry run
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text, requires = "dry_run")]
+        format: OutputFormat,
     },
 
     #[clap(name = "down")]
     #[clap(about = MIGRATE_DOWN_DESC_SHORT, long_about = MIGRATE_DOWN_DESC_LONG)]
     Down {
-        revision: Revision,
+        #[clap(required_unless_present = "steps", allow_negative_numbers = true)]
+        revision: Option<Revision>,
+
+        /// The number of migrations to revert, instead of a revision
+        #[clap(long, conflicts_with = "revision", value_parser = clap::value_parser!(i32).range(1..))]
+        steps: Option<i32>,
 
         #[clap(short, long)]
         database_url: DatabaseUrl,
+
+        /// The environment whose configured variables are used
+        #[clap(short, long, env = "MIGRATOR_ENV")]
+        env: Option<String>,
+
+        /// Set a variable substituted for the ${KEY} placeholders of the migrations
+        #[clap(long = "var", value_name = "KEY=VALUE", value_parser = crate::parse_variable)]
+        vars: Vec<(String, String)>,
+
+        /// Only print the migrations that would be reverted, without touching the database
+        #[clap(long)]
+        dry_run: bool,
+
+        /// The output format of the dry run
+        #[clap(short, long, value_enum, default_value_t = OutputFormat::Text, requires = "dry_run")]
+        format: OutputFormat,
     },
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable lines
+    Text,
+    /// Aligned columns
+    Table,
+    /// JSON document for scripts
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum CheckFormat {
+    /// Human readable lines
+    Text,
+    /// GitHub Actions workflow commands, shown as annotations on the pull request
+    Github,
+    /// JSON array of the problems
+    Json,
+}
+
 #[derive(Clone)]
 pub enum DatabaseUrl {
-    Memory,
-    File(String),
+    Sqlite(SqliteUrl),
+    Postgres(String),
+    Mysql(String),
 }
 
 impl DatabaseUrl {
     pub fn as_str(&self) -> &str {
         match self {
-            DatabaseUrl::Memory => ":memory:",
-            DatabaseUrl::File(s) => s.as_str(),
+            DatabaseUrl::Sqlite(url) => url.as_str(),
+            DatabaseUrl::Postgres(s) | DatabaseUrl::Mysql(s) => s.as_str(),
+        }
+    }
+
+    /// The dialect of the database, selecting the backend and the migrations subdirectory.
+    pub fn dialect(&self) -> Dialect {
+        match self {
+            DatabaseUrl::Sqlite(_) => Dialect::Sqlite,
+            DatabaseUrl::Postgres(_) => Dialect::Postgres,
+            DatabaseUrl::Mysql(_) => Dialect::Mysql,
         }
     }
 
-    pub fn open_connection(&self) -> rusqlite::Result<rusqlite::Connection> {
+    /// Connects to the database with the backend of its dialect, the one place backends are
+    /// created. The key unlocks SQLite databases encrypted with SQLCipher, other databases do not
+    /// use it.
+    pub fn connect(&self, key: Option<&str>) -> AnyResult<Box<dyn Backend>> {
+        if key.is_some() && self.dialect() != Dialect::Sqlite {
+            log::warn!("Ignoring the database key, only SQLite databases are encrypted");
+        }
+
         match self {
-            DatabaseUrl::Memory => rusqlite::Connection::open_in_memory(),
-            DatabaseUrl::File(s) => rusqlite::Connection::open(s),
+            DatabaseUrl::Sqlite(url) => Ok(Box::new(SqliteBackend::connect(url, key)?)),
+            DatabaseUrl::Postgres(url) => Ok(Box::new(PostgresBackend::connect(url)?)),
+            DatabaseUrl::Mysql(url) => Ok(Box::new(MysqlBackend::connect(url)?)),
         }
     }
 }
@@ -119,10 +443,95 @@ impl FromStr for DatabaseUrl {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s == "memory" {
-            Ok(DatabaseUrl::Memory)
+        if s.starts_with("postgres://") || s.starts_with("postgresql://") {
+            Ok(DatabaseUrl::Postgres(s.to_string()))
+        } else if s.starts_with("mysql://") {
+            Ok(DatabaseUrl::Mysql(s.to_string()))
+        } else if let Some(scheme) = url_scheme(s).filter(|scheme| *scheme != "sqlite") {
+            // Any other URL would be taken for the path of a new SQLite file.
+            Err(format!(
+                "Unsupported database URL scheme {}:// in {}, the supported databases are \
+                 sqlite://, postgres://, postgresql:// and mysql:// URLs, SQLite file: URIs and \
+                 paths",
+                scheme, s
+            ))
         } else {
-            Ok(DatabaseUrl::File(s.to_string()))
+            Ok(DatabaseUrl::Sqlite(s.parse()?))
+        }
+    }
+}
+
+/// The scheme of a `scheme://` URL.
+fn url_scheme(s: &str) -> Option<&str> {
+    let (scheme, _) = s.split_once("://")?;
+    let mut chars = scheme.chars();
+
+    (chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')))
+    .then_some(scheme)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_steps(args: &[&str]) -> Result<Option<i32>, clap::Error> {
+        let cli = Cli::try_parse_from(["migrator", "migrate"].iter().chain(args))?;
+
+        match cli.command {
+            Commands::Migrate(Migrate {
+                command: MigrateCommands::Up { steps, .. } | MigrateCommands::Down { steps, .. },
+            }) => Ok(steps),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn parses_steps() {
+        assert_eq!(
+            parse_steps(&["up", "--steps", "2", "-d", "memory"]).unwrap(),
+            Some(2)
+        );
+        assert_eq!(
+            parse_steps(&["down", "--steps", "1", "-d", "memory"]).unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            parse_steps(&["down", "--steps", "2147483647", "-d", "memory"]).unwrap(),
+            Some(i32::MAX)
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_steps() {
+        for steps in ["0", "-1", "2147483648", "3000000000"] {
+            assert!(parse_steps(&["up", "--steps", steps, "-d", "memory"]).is_err());
+            assert!(parse_steps(&["down", "--steps", steps, "-d", "memory"]).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_url_schemes() {
+        let dialect = |s: &str| s.parse::<DatabaseUrl>().map(|url| url.dialect());
+
+        assert_eq!(dialect("postgresql://localhost/app"), Ok(Dialect::Postgres));
+        assert_eq!(dialect("mysql://localhost/app"), Ok(Dialect::Mysql));
+        assert_eq!(dialect("sqlite://app.db?mode=rwc"), Ok(Dialect::Sqlite));
+        assert_eq!(dialect("file:app.db"), Ok(Dialect::Sqlite));
+        assert_eq!(dialect("data/app:v2.db"), Ok(Dialect::Sqlite));
+
+        for url in [
+            "duckdb://data.duckdb",
+            "mariadb://localhost/app",
+            "HTTP://x/app.db",
+        ] {
+            let err = dialect(url).unwrap_err();
+            assert!(
+                err.starts_with("Unsupported database URL scheme"),
+                "{}: {}",
+                url,
+                err
+            );
         }
     }
 }
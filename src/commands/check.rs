@@ -0,0 +1,70 @@
+use super::output::print_json;
+use crate::{
+    AnyResult, CheckIssue, Dialect, Migrator, PendingMigrations, check_migrations,
+    cli::{CheckFormat, DatabaseUrl},
+};
+
+pub fn migration_check_command(
+    migrator: &Migrator,
+    dialect: Dialect,
+    database_url: Option<DatabaseUrl>,
+    key: Option<&str>,
+    format: CheckFormat,
+) -> AnyResult<()> {
+    let issues = match database_url {
+        Some(db_url) => {
+            let mut backend = db_url.connect(key)?;
+            let current = crate::get_current_migration_id(backend.as_mut())?;
+            check_migrations(
+                migrator,
+                backend.dialect(),
+                PendingMigrations::Unapplied(current.as_ref()),
+            )?
+        }
+        None => check_migrations(migrator, dialect, PendingMigrations::Parallel)?,
+    };
+
+    match format {
+        CheckFormat::Json => print_json(&issues)?,
+        CheckFormat::Text => issues.iter().for_each(|i| println!("{}", format_text(i))),
+        CheckFormat::Github => issues.iter().for_each(|i| println!("{}", format_github(i))),
+    }
+
+    if !issues.is_empty() {
+        return Err(format!("Found {} problem(s) in the migrations", issues.len()).into());
+    }
+
+    if let CheckFormat::Text = format {
+        println!("No problems found");
+    }
+
+    Ok(())
+}
+
+fn format_text(issue: &CheckIssue) -> String {
+    match &issue.file {
+        Some(file) => format!("[{}] {}: {}", issue.kind.as_str(), file, issue.message),
+        None => format!("[{}] {}", issue.kind.as_str(), issue.message),
+    }
+}
+
+/// Formats the issue as a GitHub Actions workflow command, so it shows up as an annotation.
+fn format_github(issue: &CheckIssue) -> String {
+    let escape = |s: &str| {
+        s.replace('%', "%25")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    };
+    let escape_property = |s: &str| escape(s).replace(':', "%3A").replace(',', "%2C");
+
+    let mut properties = vec![format!("title={}", escape_property(issue.kind.as_str()))];
+    if let Some(file) = &issue.file {
+        properties.insert(0, format!("file={}", escape_property(file)));
+    }
+
+    format!(
+        "::error {}::{}",
+        properties.join(","),
+        escape(&issue.message)
+    )
+}
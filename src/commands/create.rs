@@ -0,0 +1,81 @@
+use crate::{AnyResult, Config, Dialect, Migration, MigrationGraph, MigrationTemplate, Migrator};
+
+/// Initializes a migration following the latest migration of the history, with the id and name
+/// rules of the configuration.
+fn new_migration(
+    history: &MigrationGraph,
+    config: &Config,
+    dialect: Dialect,
+    name: &str,
+) -> AnyResult<Migration> {
+    let id = config
+        .ids
+        .scheme
+        .generate(history.migrations().iter().map(|m| &m.id), config.ids.width)?;
+
+    Ok(Migration::new(id, config.names.slugify(name)?).with_dialect(dialect))
+}
+
+/// Writes the files of the migration and prints their paths.
+fn generate_files(migration: &Migration, up_sql: &str, down_sql: &str) -> AnyResult<()> {
+    for path in migration.generate_files(up_sql, down_sql)? {
+        println!("Created {}", path);
+    }
+
+    Ok(())
+}
+
+/// Creates a migration depending on the current head. With a template, the files are filled with
+/// the SQL of the template and the given placeholder values, otherwise they are left empty.
+pub fn migration_create_command(
+    migrator: &Migrator,
+    config: &Config,
+    dialect: Dialect,
+    name: String,
+    template: Option<String>,
+    values: Vec<(String, String)>,
+) -> AnyResult<()> {
+    let (up_sql, down_sql) = match template {
+        Some(template) => MigrationTemplate::find(&template)?.render(&values)?,
+        None => (String::new(), String::new()),
+    };
+
+    crate::create_migrations_dir(dialect)?;
+
+    let history = migrator.history(dialect)?;
+    let head = history.head()?;
+
+    let mut migration = new_migration(&history, config, dialect, &name)?;
+    if let Some(head) = head {
+        migration = migration.with_depends_on(vec![history.migrations()[head].id]);
+    }
+    log::debug!("Initialized migration: {:?}", migration);
+
+    generate_files(&migration, &up_sql, &down_sql)
+}
+
+/// Creates an empty migration that depends on every head, joining the branches of the history.
+pub fn migration_merge_command(
+    migrator: &Migrator,
+    config: &Config,
+    dialect: Dialect,
+    name: String,
+) -> AnyResult<()> {
+    let history = migrator.history(dialect)?;
+    let heads = history.heads();
+
+    if heads.len() < 2 {
+        return Err("There is only one head, nothing to merge".into());
+    }
+
+    let parents = heads
+        .iter()
+        .map(|head| history.migrations()[*head].id)
+        .collect::<Vec<_>>();
+
+    let migration = new_migration(&history, config, dialect, &name)?.with_depends_on(parents);
+    log::debug!("Initialized merge migration: {:?}", migration);
+
+    println!("Merging {} heads", heads.len());
+    generate_files(&migration, "", "")
+}
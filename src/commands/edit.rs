@@ -0,0 +1,42 @@
+use crate::{AnyResult, Dialect, MigrationKind, Migrator, Revision};
+
+/// Opens the up and down files of the migration in the editor of `$VISUAL` or `$EDITOR`.
+pub fn migration_edit_command(
+    migrator: &Migrator,
+    dialect: Dialect,
+    revision: Revision,
+) -> AnyResult<()> {
+    let history = migrator.history(dialect)?;
+    let migration = revision.resolve_migration(&history, None)?;
+
+    if migration.kind() == MigrationKind::Rust {
+        return Err(format!(
+            "Migration {} is a Rust migration, it has no files to edit",
+            migration.id
+        )
+        .into());
+    }
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .map_err(|_| "Set the EDITOR environment variable to the editor to open migrations with")?;
+
+    // The editor can come with arguments, e.g. `code --wait`.
+    let mut words = editor.split_whitespace();
+    let program = words
+        .next()
+        .ok_or("The EDITOR environment variable is empty")?;
+
+    let status = std::process::Command::new(program)
+        .args(words)
+        .arg(migration.up_path())
+        .arg(migration.down_path())
+        .status()
+        .map_err(|err| format!("Cannot run editor '{}': {}", editor, err))?;
+
+    if !status.success() {
+        return Err(format!("Editor '{}' exited with {}", editor, status).into());
+    }
+
+    Ok(())
+}
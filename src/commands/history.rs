@@ -1,27 +1,120 @@
-use crate::{AnyResult, cli::DatabaseUrl};
+use serde::Serialize;
 
-pub fn migration_history_command(database_url: Option<DatabaseUrl>) -> AnyResult<()> {
-    let current = if let Some(db_url) = database_url {
-        let mut conn = db_url.open_connection()?;
-        let transaction = conn.transaction()?;
-        crate::get_current_migration_id(&transaction)?
+use super::output::{print_json, print_table};
+use crate::{
+    AnyResult, Dialect, Migration, MigrationId, Migrator, cli::DatabaseUrl, cli::OutputFormat,
+};
+
+#[derive(Serialize)]
+struct HistoryOutput<'a> {
+    current: Option<MigrationId>,
+    heads: Vec<MigrationId>,
+
+    /// The migrations from the newest to the oldest.
+    migrations: Vec<HistoryMigration<'a>>,
+}
+
+#[derive(Serialize)]
+struct HistoryMigration<'a> {
+    #[serde(flatten)]
+    migration: &'a Migration,
+
+    /// The migrations of the graph this migration depends on, whether declared with
+    /// `depends-on` headers or implied by the order of the ids. Empty for the first migration.
+    parents: Vec<MigrationId>,
+}
+
+pub fn migration_history_command(
+    migrator: &Migrator,
+    dialect: Dialect,
+    database_url: Option<DatabaseUrl>,
+    key: Option<&str>,
+    format: OutputFormat,
+) -> AnyResult<()> {
+    let (dialect, current) = if let Some(db_url) = database_url {
+        let mut backend = db_url.connect(key)?;
+        (
+            backend.dialect(),
+            crate::get_current_migration_id(backend.as_mut())?,
+        )
     } else {
-        None
+        (dialect, None)
     };
 
-    let mut history = crate::get_migration_history()?;
-    history.reverse();
+    let history = migrator.history(dialect)?;
+    let heads = history.heads();
+
+    if let OutputFormat::Json = format {
+        return print_json(&HistoryOutput {
+            current,
+            heads: heads
+                .iter()
+                .map(|head| history.migrations()[*head].id)
+                .collect(),
+            migrations: history
+                .migrations()
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(index, migration)| HistoryMigration {
+                    migration,
+                    parents: history
+                        .parents(index)
+                        .iter()
+                        .map(|parent| history.migrations()[*parent].id)
+                        .collect(),
+                })
+                .collect(),
+        });
+    }
+
+    let mut rows = Vec::new();
+
+    for (index, migration) in history.migrations().iter().enumerate().rev() {
+        let mut markers = Vec::new();
 
-    for migration in history {
-        let mut text = format!("{} {}", migration.stringify_id(), migration.name());
+        if let Some(current) = &current
+            && migration.id == *current
+        {
+            markers.push("current".to_string());
+        }
+
+        if heads.len() > 1 && heads.contains(&index) {
+            markers.push("head".to_string());
+        }
 
-        if let Some(current) = &current {
-            if migration.id == *current {
-                text.push_str(" (current)");
+        if history.is_merge(index) {
+            let parents = history
+                .parents(index)
+                .iter()
+                .map(|parent| history.migrations()[*parent].stringify_id())
+                .collect::<Vec<_>>();
+            markers.push(format!("merge of {}", parents.join(", ")));
+        }
+
+        rows.push(vec![
+            migration.stringify_id(),
+            migration.name().to_string(),
+            markers.join(", "),
+        ]);
+    }
+
+    if let OutputFormat::Table = format {
+        print_table(&["ID", "NAME", "NOTES"], &rows);
+    } else {
+        for row in rows {
+            let mut text = format!("{} {}", row[0], row[1]);
+
+            if !row[2].is_empty() {
+                text.push_str(&format!(" ({})", row[2]));
             }
+
+            println!("{}", text);
         }
+    }
 
-        println!("{}", text);
+    if heads.len() > 1 {
+        eprintln!("Multiple heads found, merge them with 'migrate merge'");
     }
 
     Ok(())
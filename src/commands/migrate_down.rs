@@ -1,49 +1,93 @@
-use rusqlite::Transaction;
-
+use super::output::print_plan;
 use crate::{
-    AnyResult, Revision, create_migration_table, get_migration_history,
-    migrations::get_current_migration_id,
+    AnyResult, Backend, MigrationDirection, MigrationPlan, MigrationProgress, Migrator, Revision,
+    Variables,
+    cli::OutputFormat,
+    migrations::{get_current_migration_id, get_skipped_migration_ids},
 };
 
-pub fn migration_migrate_down(target: Revision, transaction: &Transaction<'_>) -> AnyResult<()> {
+pub fn migration_migrate_down(
+    migrator: &Migrator,
+    target: Revision,
+    variables: &Variables,
+    backend: &mut dyn Backend,
+) -> AnyResult<()> {
     log::debug!("Target revision: {:?}", target);
 
-    create_migration_table(&transaction)?;
+    backend.create_migrations_table()?;
     log::debug!("Migration table created if it didn't exist");
 
-    let all_migrations = get_migration_history()?;
-    let current = get_current_migration_id(&transaction)?;
+    let all_migrations = migrator.history(backend.dialect())?;
+    let current = get_current_migration_id(backend)?;
     log::debug!("Current migration: {:?}", current);
 
-    let revisions_to_revert = target.revisions_to_revert(&all_migrations, current.as_ref())?;
-    log::debug!("Revisions to revert: {:?}", revisions_to_revert);
+    let progress = if backend.transactional_ddl() {
+        None
+    } else {
+        Some(MigrationProgress::read(backend, MigrationDirection::Down)?)
+    };
+
+    let skipped = get_skipped_migration_ids(backend)?;
+    let plan = MigrationPlan::down(&target, &all_migrations, current.as_ref(), &skipped)?;
+    log::debug!("Revisions to revert: {:?}", plan.steps);
+
+    if let Some(progress) = &progress {
+        progress.check_plan(&plan)?;
+    }
 
-    if revisions_to_revert.is_empty() {
+    if plan.is_empty() {
         println!("Already reverted to the target revision");
         return Ok(());
     }
 
-    for migration_id in revisions_to_revert.iter() {
-        let migration = all_migrations
-            .iter()
-            .find(|m| &m.stringify_id() == migration_id)
-            .unwrap();
+    for step in plan.steps.iter() {
+        let migration = all_migrations.get(&step.id).unwrap();
 
         println!("Reverting migration: {}", migration.stringify_id());
-        migration.down(&transaction)?;
+        match &progress {
+            Some(progress) => progress.run(migration, backend, variables)?,
+            None => migration.down(backend, variables)?,
+        }
     }
     log::debug!("All migrations applied");
 
-    let last_id = target.resolve_revision_id(&all_migrations)?;
+    // Skipped migrations were never executed, so only their record is removed.
+    for step in plan.skipped.iter() {
+        println!("Unskipping migration: {}", step.id);
 
-    transaction.execute(
-        &format!(
-            "INSERT INTO {} (id) VALUES (?)",
-            crate::MIGRATIONS_TABLE_NAME
-        ),
-        [last_id],
-    )?;
+        backend.delete_migration_records(&step.id.to_string(), crate::MIGRATION_KIND_SKIPPED)?;
+    }
+
+    let last_id = plan.target_revision();
+
+    backend.insert_migration_record(&last_id, crate::MIGRATION_KIND_VERSIONED, None)?;
     log::debug!("Migration entry added to the database");
 
+    if let Some(progress) = &progress {
+        progress.clear(backend)?;
+    }
+
     Ok(())
 }
+
+/// Prints the migrations `migration_migrate_down` would revert, without touching the database.
+pub fn migration_migrate_down_dry_run(
+    migrator: &Migrator,
+    target: Revision,
+    variables: &Variables,
+    backend: &mut dyn Backend,
+    format: OutputFormat,
+) -> AnyResult<()> {
+    let all_migrations = migrator.history(backend.dialect())?;
+    let current = get_current_migration_id(backend)?;
+    let skipped = get_skipped_migration_ids(backend)?;
+    let mut plan = MigrationPlan::down(&target, &all_migrations, current.as_ref(), &skipped)?;
+    plan.load_sql(&all_migrations, variables)?;
+
+    if plan.is_empty() && matches!(format, OutputFormat::Text) {
+        println!("Already reverted to the target revision");
+        return Ok(());
+    }
+
+    print_plan(&plan, format)
+}
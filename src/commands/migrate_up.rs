@@ -1,50 +1,140 @@
-use rusqlite::Transaction;
-
+use super::output::print_plan;
 use crate::{
-    AnyResult, Revision, create_migration_table, get_migration_history,
+    AnyResult, Backend, MigrationDirection, MigrationPlan, MigrationProgress, Migrator, Revision,
+    Variables, cli::OutputFormat, get_pending_repeatable_migrations,
     migrations::get_current_migration_id,
 };
 
-pub fn migration_migrate_up(revision: Revision, transaction: &Transaction<'_>) -> AnyResult<()> {
+/// Prints a step of `migrate up`, or only logs it when `quiet`.
+fn report(quiet: bool, message: String) {
+    if quiet {
+        log::info!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// Records the migrations of the plan skipped in the environment, so that `status` lists them and
+/// `down` does not revert them.
+fn record_skipped_migrations(
+    plan: &MigrationPlan,
+    backend: &mut dyn Backend,
+    quiet: bool,
+) -> AnyResult<()> {
+    for step in plan.skipped.iter() {
+        report(quiet, format!("Skipping migration: {}", step.id));
+
+        backend.insert_migration_record(
+            &step.id.to_string(),
+            crate::MIGRATION_KIND_SKIPPED,
+            None,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Applies the migrations up to the revision. With `quiet`, the migrations applied are only
+/// logged, e.g. when several databases are migrated at once.
+pub fn migration_migrate_up(
+    migrator: &Migrator,
+    revision: Revision,
+    env: Option<&str>,
+    variables: &Variables,
+    backend: &mut dyn Backend,
+    quiet: bool,
+) -> AnyResult<()> {
     log::debug!("Target revision: {:?}", revision);
 
-    create_migration_table(&transaction)?;
+    backend.create_migrations_table()?;
     log::debug!("Migration table created if it didn't exist");
 
-    let all_migrations = get_migration_history()?;
-    let current = get_current_migration_id(&transaction)?;
+    let all_migrations = migrator.history(backend.dialect())?;
+    let current = get_current_migration_id(backend)?;
     log::debug!("Current migration: {:?}", current);
 
-    let revisions_to_apply = revision.revisions_to_apply(&all_migrations, current.as_ref())?;
-    log::debug!("Revisions to apply: {:?}", revisions_to_apply);
+    let progress = if backend.transactional_ddl() {
+        None
+    } else {
+        Some(MigrationProgress::read(backend, MigrationDirection::Up)?)
+    };
 
-    if revisions_to_apply.is_empty() {
-        println!("Already up to date");
+    let plan = MigrationPlan::up(&revision, &all_migrations, current.as_ref(), env)?;
+    log::debug!("Revisions to apply: {:?}", plan.steps);
+    log::debug!("Revisions to skip: {:?}", plan.skipped);
+
+    if let Some(progress) = &progress {
+        progress.check_plan(&plan)?;
+    }
+
+    let repeatable = get_pending_repeatable_migrations(backend, variables)?;
+
+    if plan.is_empty() && repeatable.is_empty() {
+        report(quiet, "Already up to date".to_string());
         return Ok(());
     }
 
-    for migration_id in revisions_to_apply.iter() {
-        let migration = all_migrations
-            .iter()
-            .find(|m| &m.stringify_id() == migration_id)
-            .unwrap();
+    for step in plan.steps.iter() {
+        let migration = all_migrations.get(&step.id).unwrap();
 
-        println!("Applying migration: {}", migration.stringify_id());
-        migration.up(&transaction)?;
+        report(
+            quiet,
+            format!("Applying migration: {}", migration.stringify_id()),
+        );
+        match &progress {
+            Some(progress) => progress.run(migration, backend, variables)?,
+            None => migration.up(backend, variables)?,
+        }
     }
     log::debug!("All migrations applied");
 
-    let last_id = revisions_to_apply.last().unwrap();
-    log::debug!("Setting last migration id to: {}", last_id);
+    record_skipped_migrations(&plan, backend, quiet)?;
+
+    if !plan.is_empty() {
+        let last_id = plan.target_revision();
+        log::debug!("Setting last migration id to: {}", last_id);
+
+        backend.insert_migration_record(&last_id, crate::MIGRATION_KIND_VERSIONED, None)?;
+        log::debug!("Migration entry added to the database");
+    }
 
-    transaction.execute(
-        &format!(
-            "INSERT INTO {} (id) VALUES (?)",
-            crate::MIGRATIONS_TABLE_NAME
-        ),
-        [last_id],
-    )?;
-    log::debug!("Migration entry added to the database");
+    if let Some(progress) = &progress {
+        progress.clear(backend)?;
+    }
+
+    for migration in repeatable.iter() {
+        report(
+            quiet,
+            format!("Applying repeatable migration: {}", migration.name()),
+        );
+        migration.apply(backend, variables)?;
+    }
 
     Ok(())
 }
+
+/// Prints the migrations `migration_migrate_up` would apply, without touching the database.
+pub fn migration_migrate_up_dry_run(
+    migrator: &Migrator,
+    revision: Revision,
+    env: Option<&str>,
+    variables: &Variables,
+    backend: &mut dyn Backend,
+    format: OutputFormat,
+) -> AnyResult<()> {
+    let all_migrations = migrator.history(backend.dialect())?;
+    let current = get_current_migration_id(backend)?;
+    let mut plan = MigrationPlan::up(&revision, &all_migrations, current.as_ref(), env)?;
+    plan.load_sql(&all_migrations, variables)?;
+    plan.repeatable = get_pending_repeatable_migrations(backend, variables)?
+        .iter()
+        .map(|migration| migration.name().to_string())
+        .collect();
+
+    if plan.is_empty() && plan.repeatable.is_empty() && matches!(format, OutputFormat::Text) {
+        println!("Already up to date");
+        return Ok(());
+    }
+
+    print_plan(&plan, format)
+}
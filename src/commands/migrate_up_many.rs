@@ -0,0 +1,245 @@
+use std::{
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+use super::{migration_migrate_up, output::print_table};
+use crate::{
+    AnyResult, Config, MigrationId, Migrator, Revision, Variables, cli::DatabaseUrl,
+    migrations::get_current_migration_id, run_with_transaction,
+};
+
+/// The databases migrated at once by `migrate up --database-glob` or `--database-list`, e.g. one
+/// SQLite file per tenant.
+pub struct DatabaseSet {
+    pub databases: Vec<DatabaseUrl>,
+
+    /// How many databases are migrated at the same time.
+    pub jobs: usize,
+
+    /// Whether the other databases are still migrated after one failed.
+    pub keep_going: bool,
+}
+
+impl DatabaseSet {
+    /// The databases of the glob pattern or of the list file, migrated `jobs` at a time, or as
+    /// many at a time as there are CPUs.
+    pub fn new(
+        glob: Option<String>,
+        list: Option<String>,
+        jobs: Option<u32>,
+        keep_going: bool,
+    ) -> AnyResult<Self> {
+        let databases = match (glob, list) {
+            (Some(pattern), _) => Self::glob(&pattern)?,
+            (None, Some(path)) => Self::list(&path)?,
+            (None, None) => return Err("Give the databases to migrate".into()),
+        };
+
+        let jobs = match jobs {
+            Some(jobs) => jobs as usize,
+            None => thread::available_parallelism().map_or(1, |jobs| jobs.get()),
+        };
+
+        Ok(Self {
+            databases,
+            jobs,
+            keep_going,
+        })
+    }
+
+    /// The SQLite database files matching the pattern, ordered by path.
+    fn glob(pattern: &str) -> AnyResult<Vec<DatabaseUrl>> {
+        let paths = glob::glob(pattern)
+            .map_err(|err| format!("Invalid database pattern {}: {}", pattern, err))?;
+
+        let mut databases = Vec::new();
+        for path in paths {
+            let path = path?;
+            if path.is_file() {
+                databases.push(DatabaseUrl::Sqlite(path.to_string_lossy().parse()?));
+            }
+        }
+
+        if databases.is_empty() {
+            return Err(format!("No database file matches {}", pattern).into());
+        }
+
+        Ok(databases)
+    }
+
+    /// The databases of the file, one URL or path per line. Blank lines and lines starting with
+    /// `#` are ignored.
+    fn list(path: &str) -> AnyResult<Vec<DatabaseUrl>> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("Cannot read database list {}: {}", path, err))?;
+
+        let databases = content
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(index, line)| {
+                line.parse::<DatabaseUrl>()
+                    .map_err(|err| format!("{}:{}: {}", path, index + 1, err))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if databases.is_empty() {
+            return Err(format!("Database list {} is empty", path).into());
+        }
+
+        Ok(databases)
+    }
+}
+
+/// What happened to one database of the set.
+#[derive(Clone)]
+struct Outcome {
+    /// The revision of the database before the migrations, unknown if it could not be read.
+    from: Option<String>,
+
+    /// The revision the database was migrated to, `None` if migrating it failed.
+    to: Option<String>,
+    error: Option<String>,
+}
+
+/// Applies the migrations up to the revision to every database of the set, each in its own
+/// transaction, and prints the revisions every database went from and to. Unless `keep_going`,
+/// no database is started after one failed.
+pub fn migration_migrate_up_many(
+    migrator: &Migrator,
+    config: &Config,
+    set: DatabaseSet,
+    key: Option<&str>,
+    revision: Revision,
+    env: Option<&str>,
+    variables: &Variables,
+) -> AnyResult<()> {
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let done = AtomicUsize::new(0);
+    let outcomes = Mutex::new(vec![None; set.databases.len()]);
+
+    thread::scope(|scope| {
+        for _ in 0..set.jobs.min(set.databases.len()) {
+            scope.spawn(|| {
+                while !stop.load(Ordering::SeqCst) {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(database) = set.databases.get(index) else {
+                        break;
+                    };
+
+                    let outcome = migrate_database(
+                        migrator, config, database, key, &revision, env, variables,
+                    );
+                    if outcome.error.is_some() && !set.keep_going {
+                        stop.store(true, Ordering::SeqCst);
+                    }
+
+                    log::info!(
+                        "[{}/{}] {}: {}",
+                        done.fetch_add(1, Ordering::SeqCst) + 1,
+                        set.databases.len(),
+                        database.as_str(),
+                        outcome.error.as_deref().unwrap_or("ok")
+                    );
+                    outcomes.lock().unwrap()[index] = Some(outcome);
+                }
+            });
+        }
+    });
+
+    let outcomes = outcomes.into_inner().unwrap();
+    let unknown = || "-".to_string();
+
+    let rows = set
+        .databases
+        .iter()
+        .zip(outcomes.iter())
+        .map(|(database, outcome)| match outcome {
+            Some(outcome) => vec![
+                database.as_str().to_string(),
+                outcome.from.clone().unwrap_or_else(unknown),
+                outcome.to.clone().unwrap_or_else(unknown),
+                match &outcome.error {
+                    Some(error) => format!("failed: {}", error.lines().next().unwrap_or_default()),
+                    None => "ok".to_string(),
+                },
+            ],
+            None => vec![
+                database.as_str().to_string(),
+                unknown(),
+                unknown(),
+                "not run".to_string(),
+            ],
+        })
+        .collect::<Vec<_>>();
+    print_table(&["DATABASE", "FROM", "TO", "RESULT"], &rows);
+
+    let failed = outcomes
+        .iter()
+        .flatten()
+        .filter(|outcome| outcome.error.is_some())
+        .count();
+    let not_run = outcomes.iter().filter(|outcome| outcome.is_none()).count();
+    let migrated = outcomes.len() - failed - not_run;
+
+    println!(
+        "\nMigrated: {}, failed: {}, not run: {}",
+        migrated, failed, not_run
+    );
+
+    if failed > 0 {
+        return Err(format!(
+            "Migrating {} of {} database(s) failed",
+            failed,
+            outcomes.len()
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+fn migrate_database(
+    migrator: &Migrator,
+    config: &Config,
+    database: &DatabaseUrl,
+    key: Option<&str>,
+    revision: &Revision,
+    env: Option<&str>,
+    variables: &Variables,
+) -> Outcome {
+    let describe = |id: Option<MigrationId>| {
+        id.map_or(crate::MIGRATION_BASE_REVISION.to_string(), |id| {
+            id.to_string()
+        })
+    };
+
+    let mut from = None;
+    let result = run_with_transaction(database.clone(), key, |backend| {
+        migrator.prepare_backend(config, backend)?;
+        from = Some(describe(get_current_migration_id(backend)?));
+
+        migration_migrate_up(migrator, revision.clone(), env, variables, backend, true)?;
+
+        Ok(describe(get_current_migration_id(backend)?))
+    });
+
+    match result {
+        Ok(to) => Outcome {
+            from,
+            to: Some(to),
+            error: None,
+        },
+        Err(err) => Outcome {
+            from,
+            to: None,
+            error: Some(err.to_string()),
+        },
+    }
+}
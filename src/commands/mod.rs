@@ -1,7 +1,30 @@
+mod check;
+mod create;
+mod edit;
 mod history;
 mod migrate_down;
 mod migrate_up;
+mod migrate_up_many;
+mod output;
+mod rekey;
+mod remove;
+mod rename;
+mod seed;
+mod show;
+mod status;
+mod test;
 
+pub use check::*;
+pub use create::*;
+pub use edit::*;
 pub use history::*;
 pub use migrate_down::*;
 pub use migrate_up::*;
+pub use migrate_up_many::*;
+pub use rekey::*;
+pub use remove::*;
+pub use rename::*;
+pub use seed::*;
+pub use show::*;
+pub use status::*;
+pub use test::*;
@@ -0,0 +1,91 @@
+use serde::Serialize;
+
+use crate::{AnyResult, MigrationDirection, MigrationPlan, cli::OutputFormat};
+
+pub(crate) fn print_json<T: Serialize>(value: &T) -> AnyResult<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Prints the rows as a table, with every column padded to its widest cell.
+pub(crate) fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let headers = headers.iter().map(|h| h.to_string()).collect::<Vec<_>>();
+
+    let mut widths = headers
+        .iter()
+        .map(|h| h.chars().count())
+        .collect::<Vec<_>>();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let separator = widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>();
+
+    for row in [&headers, &separator].into_iter().chain(rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+}
+
+pub(crate) fn print_plan(plan: &MigrationPlan, format: OutputFormat) -> AnyResult<()> {
+    let (action, skip_action) = match plan.direction {
+        MigrationDirection::Up => ("apply", "skip"),
+        MigrationDirection::Down => ("revert", "unskip"),
+    };
+
+    match format {
+        OutputFormat::Json => print_json(plan)?,
+        OutputFormat::Table => {
+            let rows = plan
+                .steps
+                .iter()
+                .map(|step| (action, step.id.to_string(), step.name.clone()))
+                .chain(
+                    plan.skipped
+                        .iter()
+                        .map(|step| (skip_action, step.id.to_string(), step.name.clone())),
+                )
+                .chain(
+                    plan.repeatable
+                        .iter()
+                        .map(|name| ("reapply", String::new(), name.clone())),
+                )
+                .enumerate()
+                .map(|(index, (action, id, name))| {
+                    vec![(index + 1).to_string(), action.to_string(), id, name]
+                })
+                .collect::<Vec<_>>();
+            print_table(&["STEP", "ACTION", "ID", "NAME"], &rows);
+        }
+        OutputFormat::Text => {
+            for step in plan.steps.iter() {
+                println!("Would {} migration: {} {}", action, step.id, step.name);
+
+                if let Some(sql) = step.sql.as_deref().map(str::trim)
+                    && !sql.is_empty()
+                {
+                    for line in sql.lines() {
+                        println!("    {}", line);
+                    }
+                }
+            }
+
+            for step in plan.skipped.iter() {
+                println!("Would {} migration: {} {}", skip_action, step.id, step.name);
+            }
+
+            for name in plan.repeatable.iter() {
+                println!("Would apply repeatable migration: {}", name);
+            }
+        }
+    }
+
+    Ok(())
+}
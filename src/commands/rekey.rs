@@ -0,0 +1,46 @@
+use crate::{AnyResult, Dialect, SqliteBackend, cli::DatabaseUrl, config::read_key_file};
+
+/// Changes the key of a SQLCipher encrypted database. The new key is read from `new_key_file`,
+/// or from the `MIGRATOR_DB_NEW_KEY` environment variable.
+pub fn migration_rekey_command(
+    database_url: DatabaseUrl,
+    key: Option<&str>,
+    new_key_file: Option<String>,
+) -> AnyResult<()> {
+    if database_url.dialect() != Dialect::Sqlite {
+        return Err("Only SQLite databases encrypted with SQLCipher can be rekeyed".into());
+    }
+
+    let Some(key) = key else {
+        return Err(format!(
+            "The current key of the database is needed, give it with --key-file, {} or the \
+             configuration",
+            crate::MIGRATOR_DB_KEY_ENV
+        )
+        .into());
+    };
+
+    let new_key = match new_key_file {
+        Some(path) => read_key_file(&path)?,
+        None => std::env::var(crate::MIGRATOR_DB_NEW_KEY_ENV).map_err(|_| {
+            format!(
+                "Give the new key with --new-key-file or {}",
+                crate::MIGRATOR_DB_NEW_KEY_ENV
+            )
+        })?,
+    };
+
+    if new_key.is_empty() {
+        return Err("The new key is empty, decrypting databases is not supported".into());
+    }
+
+    let mut backend = database_url.connect(Some(key))?;
+    backend
+        .downcast_mut::<SqliteBackend>()
+        .unwrap()
+        .rekey(&new_key)?;
+
+    println!("Changed the key of {}", database_url.as_str());
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use crate::{
+    AnyResult, Dialect, MigrationHeaders, MigrationKind, MigrationState, MigrationStatus, Migrator,
+    Revision, cli::DatabaseUrl,
+};
+
+/// Deletes the up and down files of the migration. The migration must not be applied to any of
+/// the given databases, and must be a head unless `force` is given, in which case the migrations
+/// depending on it are made to depend on its parents instead.
+pub fn migration_remove_command(
+    migrator: &Migrator,
+    dialect: Dialect,
+    revision: Revision,
+    database_urls: Vec<DatabaseUrl>,
+    key: Option<&str>,
+    force: bool,
+) -> AnyResult<()> {
+    let dialect = database_urls.first().map_or(dialect, DatabaseUrl::dialect);
+    if let Some(db_url) = database_urls
+        .iter()
+        .find(|db_url| db_url.dialect() != dialect)
+    {
+        return Err(format!(
+            "Database {} is a {} database, the others are {} databases",
+            db_url.as_str(),
+            db_url.dialect(),
+            dialect
+        )
+        .into());
+    }
+
+    let history = migrator.history(dialect)?;
+    let migration = revision.resolve_migration(&history, None)?;
+    let index = history.index_of(&migration.id).unwrap();
+
+    if migration.kind() == MigrationKind::Rust {
+        return Err(format!(
+            "Migration {} is a Rust migration, remove it from the code registering it",
+            migration.id
+        )
+        .into());
+    }
+
+    for db_url in database_urls.iter() {
+        let mut backend = db_url.connect(key)?;
+        let status = MigrationStatus::read(&history, backend.as_mut())?;
+
+        if let Some(state @ (MigrationState::Applied | MigrationState::Skipped)) =
+            status.state(&migration.id)
+        {
+            return Err(format!(
+                "Migration {} is recorded as {} in {}, revert it first",
+                migration.id,
+                state.as_str(),
+                db_url.as_str()
+            )
+            .into());
+        }
+    }
+
+    let children = history.children(index);
+    if !children.is_empty() && !force {
+        let children = children
+            .iter()
+            .map(|child| history.migrations()[*child].stringify_id())
+            .collect::<Vec<_>>();
+
+        return Err(format!(
+            "Migration {} is not the latest, {} depend(s) on it, use --force to remove it anyway",
+            migration.id,
+            children.join(", ")
+        )
+        .into());
+    }
+
+    let parents = history
+        .parents(index)
+        .iter()
+        .map(|parent| history.migrations()[*parent].id)
+        .collect::<Vec<_>>();
+
+    let children = children
+        .iter()
+        .map(|child| &history.migrations()[*child])
+        .filter(|child| child.depends_on().contains(&migration.id))
+        .collect::<Vec<_>>();
+
+    if let Some(child) = children
+        .iter()
+        .find(|child| child.kind() == MigrationKind::Rust)
+    {
+        return Err(format!(
+            "Rust migration {} depends on migration {}, change its depends_on first",
+            child.id, migration.id
+        )
+        .into());
+    }
+
+    // Every header is rewritten before any file is replaced, so that no child is left pointing
+    // to the removed migration while others are not.
+    let mut rewritten = Vec::new();
+    for child in children {
+        let mut depends_on = child
+            .depends_on()
+            .iter()
+            .filter(|id| **id != migration.id)
+            .copied()
+            .collect::<Vec<_>>();
+        for parent in parents.iter() {
+            if !depends_on.contains(parent) {
+                depends_on.push(*parent);
+            }
+        }
+
+        let path = child.up_path();
+        let sql = std::fs::read_to_string(&path)?;
+        rewritten.push((
+            path,
+            MigrationHeaders::replace(&sql, crate::MIGRATION_DEPENDS_ON_HEADER, &depends_on),
+        ));
+    }
+
+    crate::utils::replace_files(&rewritten)?;
+    for (path, _) in rewritten.iter() {
+        println!("Updated the depends-on header of {}", path);
+    }
+
+    for path in [migration.up_path(), migration.down_path()] {
+        std::fs::remove_file(&path).map_err(|err| format!("Cannot remove {}: {}", path, err))?;
+        println!("Removed {}", path);
+    }
+
+    Ok(())
+}
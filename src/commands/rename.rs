@@ -0,0 +1,88 @@
+use crate::{
+    AnyResult, Config, Dialect, Migration, MigrationKind, MigrationState, MigrationStatus,
+    Migrator, Revision, cli::DatabaseUrl,
+};
+
+/// Renames the up and down files of the migration, keeping its id. With a database, migrations
+/// recorded as applied to it are only renamed with `force`.
+#[allow(clippy::too_many_arguments)]
+pub fn migration_rename_command(
+    migrator: &Migrator,
+    config: &Config,
+    dialect: Dialect,
+    revision: Revision,
+    new_name: String,
+    database_url: Option<DatabaseUrl>,
+    key: Option<&str>,
+    force: bool,
+) -> AnyResult<()> {
+    let dialect = database_url.as_ref().map_or(dialect, DatabaseUrl::dialect);
+    let history = migrator.history(dialect)?;
+
+    let (migration, state) = match database_url {
+        Some(db_url) => {
+            let mut backend = db_url.connect(key)?;
+            let status = MigrationStatus::read(&history, backend.as_mut())?;
+            let migration = revision.resolve_migration(&history, status.current.as_ref())?;
+
+            (migration, status.state(&migration.id))
+        }
+        None => (revision.resolve_migration(&history, None)?, None),
+    };
+
+    if migration.kind() == MigrationKind::Rust {
+        return Err(format!(
+            "Migration {} is a Rust migration, rename it in its code",
+            migration.id
+        )
+        .into());
+    }
+
+    if let Some(state @ (MigrationState::Applied | MigrationState::Skipped)) = state
+        && !force
+    {
+        return Err(format!(
+            "Migration {} is recorded as {} in the database, use --force to rename it anyway",
+            migration.id,
+            state.as_str()
+        )
+        .into());
+    }
+
+    let renamed = Migration::new(migration.id, config.names.slugify(&new_name)?)
+        .with_dialect(migration.dialect());
+    if renamed.name() == migration.name() {
+        println!(
+            "Migration {} is already named {}",
+            migration.id,
+            migration.name()
+        );
+        return Ok(());
+    }
+
+    let moves = [
+        (migration.up_path(), renamed.up_path()),
+        (migration.down_path(), renamed.down_path()),
+    ];
+
+    for (_, to) in moves.iter() {
+        if std::path::Path::new(to).exists() {
+            return Err(format!("Refusing to overwrite existing file: {}", to).into());
+        }
+    }
+
+    for (index, (from, to)) in moves.iter().enumerate() {
+        if let Err(err) = std::fs::rename(from, to) {
+            // Put back the files renamed so far, so the migration keeps matching files.
+            for (from, to) in moves[..index].iter() {
+                let _ = std::fs::rename(to, from);
+            }
+
+            return Err(format!("Cannot rename {}: {}", from, err).into());
+        }
+
+        println!("Renamed {} to {}", from, to);
+    }
+
+    Ok(())
+}
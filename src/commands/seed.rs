@@ -0,0 +1,65 @@
+use crate::{AnyResult, Backend, MigrationState, MigrationStatus, Migrator, Variables, get_seeds};
+
+/// Runs the seeds that apply to the environment and have not run against the database yet, or
+/// only the seeds with the given names. Seeds that already ran are run again with `force`.
+pub fn migration_seed_command(
+    migrator: &Migrator,
+    backend: &mut dyn Backend,
+    env: Option<String>,
+    names: Vec<String>,
+    force: bool,
+    variables: &Variables,
+) -> AnyResult<()> {
+    let history = migrator.history(backend.dialect())?;
+    let status = MigrationStatus::read(&history, backend)?;
+
+    let pending = status.count(MigrationState::Pending);
+    if pending > 0 {
+        return Err(format!(
+            "The database has {} pending migration(s), run 'migrate up' before seeding",
+            pending
+        )
+        .into());
+    }
+
+    let seeds = get_seeds(backend.dialect())?;
+
+    if let Some(unknown) = names
+        .iter()
+        .find(|name| !seeds.iter().any(|seed| seed.name() == name.as_str()))
+    {
+        return Err(format!("Seed not found: {}", unknown).into());
+    }
+
+    let mut seeded = 0;
+
+    for seed in seeds.iter() {
+        if !names.is_empty() && !names.iter().any(|name| name == seed.name()) {
+            continue;
+        }
+
+        if !seed.applies_to(env.as_deref()) {
+            log::debug!(
+                "Skipping seed {}, restricted to: {}",
+                seed.name(),
+                seed.environments().join(", ")
+            );
+            continue;
+        }
+
+        if !force && seed.is_seeded(backend)? {
+            log::debug!("Skipping seed {}, already seeded", seed.name());
+            continue;
+        }
+
+        println!("Seeding: {}", seed.name());
+        seed.run(backend, variables)?;
+        seeded += 1;
+    }
+
+    if seeded == 0 {
+        println!("Nothing to seed");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use crate::{AnyResult, Dialect, MigrationKind, Migrator, Revision};
+
+/// Prints the metadata and both SQL bodies of the migration.
+pub fn migration_show_command(
+    migrator: &Migrator,
+    dialect: Dialect,
+    revision: Revision,
+) -> AnyResult<()> {
+    let history = migrator.history(dialect)?;
+    let migration = revision.resolve_migration(&history, None)?;
+    let index = history.index_of(&migration.id).unwrap();
+
+    println!("Migration: {} {}", migration.id, migration.name());
+
+    if let Some(created_at) = migration.created_at() {
+        println!("Created: {}", created_at);
+    }
+
+    let parents = history
+        .parents(index)
+        .iter()
+        .map(|parent| history.migrations()[*parent].stringify_id())
+        .collect::<Vec<_>>();
+    if !parents.is_empty() {
+        println!("Depends on: {}", parents.join(", "));
+    }
+
+    if !migration.environments().is_empty() {
+        println!("Environments: {}", migration.environments().join(", "));
+    }
+
+    if !migration.extensions().is_empty() {
+        println!("Requires extensions: {}", migration.extensions().join(", "));
+    }
+
+    if migration.kind() == MigrationKind::Rust {
+        println!("Kind: rust, registered with the migrator");
+        return Ok(());
+    }
+
+    for path in [migration.up_path(), migration.down_path()] {
+        println!();
+        println!("==> {} <==", path);
+        print!("{}", std::fs::read_to_string(&path)?);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use super::output::{print_json, print_table};
+use crate::{AnyResult, Backend, MigrationState, MigrationStatus, Migrator, cli::OutputFormat};
+
+pub fn migration_status_command(
+    migrator: &Migrator,
+    backend: &mut dyn Backend,
+    format: OutputFormat,
+) -> AnyResult<()> {
+    let history = migrator.history(backend.dialect())?;
+    let status = MigrationStatus::read(&history, backend)?;
+
+    match format {
+        OutputFormat::Json => print_json(&status)?,
+        OutputFormat::Table => {
+            let rows = status
+                .migrations
+                .iter()
+                .rev()
+                .map(|m| {
+                    vec![
+                        m.id.to_string(),
+                        m.name.clone(),
+                        m.state.as_str().to_string(),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            print_table(&["ID", "NAME", "STATE"], &rows);
+        }
+        OutputFormat::Text => {
+            let describe = |id: &crate::MigrationId| match history.get(id) {
+                Some(migration) => format!("{} {}", id, migration.name()),
+                None => id.to_string(),
+            };
+
+            match &status.current {
+                Some(current) => println!("Current: {}", describe(current)),
+                None => println!("Current: none"),
+            }
+
+            for head in status.heads.iter() {
+                println!("Head: {}", describe(head));
+            }
+
+            println!(
+                "Applied: {}, skipped: {}, pending: {}",
+                status.count(MigrationState::Applied),
+                status.count(MigrationState::Skipped),
+                status.count(MigrationState::Pending)
+            );
+
+            for (state, title) in [
+                (MigrationState::Skipped, "Skipped migrations:"),
+                (MigrationState::Pending, "Pending migrations:"),
+            ] {
+                let mut migrations = status
+                    .migrations
+                    .iter()
+                    .filter(|m| m.state == state)
+                    .peekable();
+
+                if migrations.peek().is_some() {
+                    println!();
+                    println!("{}", title);
+                    for migration in migrations {
+                        println!("  {} {}", migration.id, migration.name);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
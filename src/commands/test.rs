@@ -0,0 +1,224 @@
+use rusqlite::{Connection, types::ValueRef};
+
+use super::{migration_migrate_down, migration_migrate_up};
+use crate::{
+    AnyResult, Backend, Config, Dialect, Migrator, RelativeRevision, Revision, SqliteBackend,
+    SqliteUrl, Variables, cli::DatabaseUrl, migrations::get_current_migration_id,
+    run_with_transaction, split_statements,
+};
+
+/// Applies every migration to a scratch SQLite database, in memory or in a temporary file, and
+/// runs the assertions of the `tests` directory against it. With `roundtrip`, the migrations are
+/// reverted to base and applied again first, checking that the down migrations restore the
+/// schema.
+pub fn migration_test_command(
+    migrator: &Migrator,
+    config: &Config,
+    dialect: Dialect,
+    env: Option<&str>,
+    variables: &Variables,
+    roundtrip: bool,
+    temp_file: bool,
+) -> AnyResult<()> {
+    if dialect != Dialect::Sqlite {
+        return Err(format!(
+            "migrate test runs the migrations against a scratch SQLite database, the {} dialect \
+             is not supported",
+            dialect
+        )
+        .into());
+    }
+
+    // The file is created in a new directory only the user can access, removed together with
+    // the file and its journals when dropped, whether the test passes or not.
+    let temp_dir = if temp_file {
+        Some(
+            tempfile::Builder::new()
+                .prefix("migrator-test-")
+                .tempdir()?,
+        )
+    } else {
+        None
+    };
+    let url = match &temp_dir {
+        Some(dir) => dir
+            .path()
+            .join("test.sqlite")
+            .to_string_lossy()
+            .parse::<SqliteUrl>()?,
+        None => "memory".parse::<SqliteUrl>()?,
+    };
+
+    run_with_transaction(DatabaseUrl::Sqlite(url), None, |backend| {
+        migrator.prepare_backend(config, backend)?;
+        test_migrations(migrator, dialect, env, variables, roundtrip, backend)
+    })
+}
+
+fn test_migrations(
+    migrator: &Migrator,
+    dialect: Dialect,
+    env: Option<&str>,
+    variables: &Variables,
+    roundtrip: bool,
+    backend: &mut dyn Backend,
+) -> AnyResult<()> {
+    let head = Revision::Relative(RelativeRevision::Head(0));
+    let empty_schema = schema(backend)?;
+
+    migration_migrate_up(migrator, head.clone(), env, variables, backend, false)?;
+
+    if roundtrip && get_current_migration_id(backend)?.is_none() {
+        println!("No migrations were applied, nothing to revert and apply again");
+    } else if roundtrip {
+        let head_schema = schema(backend)?;
+
+        migration_migrate_down(migrator, Revision::Base, variables, backend)?;
+        // Views and triggers may be left by repeatable migrations, which are not reverted.
+        let tables = |schema: Vec<SchemaObject>| {
+            schema
+                .into_iter()
+                .filter(|(kind, _, _)| kind == "table" || kind == "index")
+                .collect::<Vec<_>>()
+        };
+        if let Some(difference) =
+            schema_difference(&tables(empty_schema), &tables(schema(backend)?))
+        {
+            return Err(format!(
+                "Reverting every migration did not restore the empty schema: {}",
+                difference
+            )
+            .into());
+        }
+
+        migration_migrate_up(migrator, head, env, variables, backend, false)?;
+        if let Some(difference) = schema_difference(&head_schema, &schema(backend)?) {
+            return Err(format!(
+                "Applying the migrations again after reverting them changed the schema: {}",
+                difference
+            )
+            .into());
+        }
+
+        println!("Reverted every migration and applied them again");
+    }
+
+    let tests = crate::utils::read_sql_files(&dialect.tests_dir())?;
+    if tests.is_empty() {
+        println!("No assertions in {}", dialect.tests_dir());
+        return Ok(());
+    }
+
+    let connection = backend
+        .downcast_mut::<SqliteBackend>()
+        .unwrap()
+        .connection();
+
+    let mut failed = 0;
+    for (name, sql) in tests.iter() {
+        // Every assertion sees the database as the migrations left it.
+        connection.execute_batch("SAVEPOINT migrator_test")?;
+        let result = run_assertion(connection, &variables.substitute(sql)?);
+        connection.execute_batch("ROLLBACK TO migrator_test; RELEASE migrator_test")?;
+
+        match result {
+            Ok(()) => println!("ok      {}", name),
+            Err(message) => {
+                println!("FAILED  {}: {}", name, message);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{} of {} assertion(s) failed", failed, tests.len()).into());
+    }
+
+    println!("All {} assertion(s) passed", tests.len());
+
+    Ok(())
+}
+
+/// Runs the statements of an assertion. It fails if a statement fails, or if a query returns a
+/// row whose first column is NULL, zero or empty.
+fn run_assertion(connection: &Connection, sql: &str) -> Result<(), String> {
+    for (index, statement) in split_statements(sql, Dialect::Sqlite).iter().enumerate() {
+        let failed = |message: String| format!("statement {}: {}", index + 1, message);
+
+        let mut prepared = connection
+            .prepare(statement)
+            .map_err(|err| failed(err.to_string()))?;
+        if prepared.column_count() == 0 {
+            prepared
+                .raw_execute()
+                .map_err(|err| failed(err.to_string()))?;
+            continue;
+        }
+
+        let mut rows = prepared.raw_query();
+        while let Some(row) = rows.next().map_err(|err| failed(err.to_string()))? {
+            let holds = match row.get_ref(0).map_err(|err| failed(err.to_string()))? {
+                ValueRef::Null => false,
+                ValueRef::Integer(value) => value != 0,
+                ValueRef::Real(value) => value != 0.0,
+                ValueRef::Text(value) | ValueRef::Blob(value) => !value.is_empty(),
+            };
+
+            if !holds {
+                return Err(failed(format!("returned a false row: {}", statement)));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The type, name and SQL definition of an object of the schema.
+type SchemaObject = (String, String, Option<String>);
+
+/// The schema objects of the database, leaving out the tables of the migrator and SQLite.
+fn schema(backend: &mut dyn Backend) -> AnyResult<Vec<SchemaObject>> {
+    let connection = backend
+        .downcast_mut::<SqliteBackend>()
+        .unwrap()
+        .connection();
+
+    let mut statement = connection.prepare(
+        "SELECT type, name, sql FROM sqlite_master
+        WHERE tbl_name NOT IN (?1, ?2) AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\'
+        ORDER BY type, name",
+    )?;
+    let objects = statement
+        .query_map(
+            [crate::MIGRATIONS_TABLE_NAME, crate::SEEDS_TABLE_NAME],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(objects)
+}
+
+/// Describes the objects only one of the schemas has, or has with another definition.
+fn schema_difference(expected: &[SchemaObject], actual: &[SchemaObject]) -> Option<String> {
+    let describe = |objects: &[SchemaObject], others: &[SchemaObject]| {
+        objects
+            .iter()
+            .filter(|object| !others.contains(object))
+            .map(|(kind, name, _)| format!("{} {}", kind, name))
+            .collect::<Vec<_>>()
+    };
+
+    let missing = describe(expected, actual);
+    let unexpected = describe(actual, expected);
+
+    match (missing.is_empty(), unexpected.is_empty()) {
+        (true, true) => None,
+        (false, true) => Some(format!("missing {}", missing.join(", "))),
+        (true, false) => Some(format!("left {}", unexpected.join(", "))),
+        (false, false) => Some(format!(
+            "missing {}, left {}",
+            missing.join(", "),
+            unexpected.join(", ")
+        )),
+    }
+}
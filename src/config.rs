@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::{AnyResult, Backend, Dialect, IdScheme, NamesConfig};
+
+/// The settings of the `migrator.toml` file at the root of the project, e.g.
+///
+/// ```toml
+/// dialect = "sqlite"
+///
+/// [ids]
+/// scheme = "sequential"
+/// width = 4
+///
+/// [names]
+/// separator = "-"
+/// lowercase = true
+///
+/// [sqlite]
+/// key_file = ".migrator-key"
+/// extensions = ["mod_spatialite"]
+///
+/// [vars]
+/// retention_days = "30"
+///
+/// [env.prod.vars]
+/// retention_days = "365"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The dialect of the migrations commands work with when they are not given a database.
+    pub dialect: Option<Dialect>,
+
+    /// How the ids of new migrations are generated.
+    pub ids: IdsConfig,
+
+    /// How the names of new migrations are turned into filenames.
+    pub names: NamesConfig,
+
+    /// Settings of SQLite databases.
+    pub sqlite: SqliteConfig,
+
+    /// The values of the variables substituted in the migrations.
+    pub vars: BTreeMap<String, String>,
+
+    /// Settings overriding the ones above in a given environment.
+    pub env: BTreeMap<String, EnvConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdsConfig {
+    pub scheme: IdScheme,
+
+    /// The number of digits sequential ids are padded to.
+    pub width: usize,
+}
+
+impl Default for IdsConfig {
+    fn default() -> Self {
+        Self {
+            scheme: IdScheme::default(),
+            width: 4,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SqliteConfig {
+    /// The file holding the key of SQLCipher encrypted databases.
+    pub key_file: Option<String>,
+
+    /// The extensions loaded before any migration or seed runs, see
+    /// [`crate::Backend::load_extension`].
+    pub extensions: Vec<String>,
+}
+
+impl SqliteConfig {
+    /// Loads the configured extensions into a SQLite database. Other databases are left alone.
+    pub fn load_extensions(&self, backend: &mut dyn Backend) -> AnyResult<()> {
+        if backend.dialect() != Dialect::Sqlite {
+            return Ok(());
+        }
+
+        for extension in self.extensions.iter() {
+            backend.load_extension(extension)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnvConfig {
+    pub vars: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// Reads the configuration file. Returns the default configuration if there is none.
+    pub fn load() -> AnyResult<Self> {
+        let content = match std::fs::read_to_string(crate::MIGRATOR_CONFIG_FILE) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err.into()),
+        };
+
+        toml::from_str(&content)
+            .map_err(|err| format!("Invalid {}: {}", crate::MIGRATOR_CONFIG_FILE, err).into())
+    }
+
+    /// The key of SQLCipher encrypted databases, from the given key file, the `MIGRATOR_DB_KEY`
+    /// environment variable or the key file of the configuration, in this order.
+    pub fn database_key(&self, key_file: Option<&str>) -> AnyResult<Option<String>> {
+        if let Some(key_file) = key_file {
+            return read_key_file(key_file).map(Some);
+        }
+
+        if let Ok(key) = std::env::var(crate::MIGRATOR_DB_KEY_ENV) {
+            return Ok(Some(key));
+        }
+
+        self.sqlite
+            .key_file
+            .as_deref()
+            .map(read_key_file)
+            .transpose()
+    }
+
+    /// The variables of the configuration in the given environment.
+    pub fn vars(&self, env: Option<&str>) -> BTreeMap<String, String> {
+        let mut vars = self.vars.clone();
+
+        if let Some(env) = env.and_then(|env| self.env.get(env)) {
+            vars.extend(env.vars.clone());
+        }
+
+        vars
+    }
+}
+
+/// Reads a key file, ignoring the line break at its end.
+pub(crate) fn read_key_file(path: &str) -> AnyResult<String> {
+    let key = std::fs::read_to_string(path)
+        .map_err(|err| format!("Cannot read key file {}: {}", path, err))?;
+    let key = key.trim_end_matches(['\n', '\r']);
+
+    if key.is_empty() {
+        return Err(format!("Key file {} is empty", path).into());
+    }
+
+    Ok(key.to_string())
+}
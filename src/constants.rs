@@ -1,22 +1,75 @@
 use constcat::concat;
 
 macro_rules! join_dirs {
-    ($($dir:expr),*) => {
-        concat!($($dir, "/"),*)
+    ($first:expr $(, $dir:expr)*) => {
+        concat!($first $(, "/", $dir)*)
     };
 }
 
 pub const MIGRATIONS_TABLE_NAME: &str = "__migrations__";
 
+/// The revision recorded in the migrations table when every migration has been reverted.
+pub const MIGRATION_BASE_REVISION: &str = "base";
+
+/// The kinds of rows in the migrations table: versioned rows record the current migration,
+/// repeatable rows the checksum a repeatable migration was last applied with and skipped rows the
+/// migrations passed over because they are restricted to other environments. Partial rows record
+/// the statements completed by a run on a backend without transactional DDL, see
+/// [`crate::MigrationProgress`].
+pub const MIGRATION_KIND_VERSIONED: &str = "versioned";
+pub const MIGRATION_KIND_REPEATABLE: &str = "repeatable";
+pub const MIGRATION_KIND_SKIPPED: &str = "skipped";
+pub const MIGRATION_KIND_PARTIAL_UP: &str = "partial-up";
+pub const MIGRATION_KIND_PARTIAL_DOWN: &str = "partial-down";
+
 pub const CREATE_MIGRATIONS_TABLE_SQL: &str = concat!(
     "CREATE TABLE IF NOT EXISTS ",
     MIGRATIONS_TABLE_NAME,
     " (
-        migrated_at TIMESTAMP NOT NULL PRIMARY KEY DEFAULT CURRENT_TIMESTAMP,
-        id TEXT NOT NULL
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        migrated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        id TEXT NOT NULL,
+        kind TEXT NOT NULL DEFAULT '",
+    MIGRATION_KIND_VERSIONED,
+    "',
+        checksum TEXT
     )"
 );
 
+/// Adds the columns introduced by repeatable migrations to migrations tables created before.
+pub const ADD_MIGRATIONS_TABLE_KIND_COLUMNS_SQL: &str = concat!(
+    "ALTER TABLE ",
+    MIGRATIONS_TABLE_NAME,
+    " ADD COLUMN kind TEXT NOT NULL DEFAULT '",
+    MIGRATION_KIND_VERSIONED,
+    "';
+    ALTER TABLE ",
+    MIGRATIONS_TABLE_NAME,
+    " ADD COLUMN checksum TEXT;"
+);
+
+/// Migrations tables created before the `seq` column existed used `migrated_at` as the primary
+/// key, which prevented recording two migrations within the same second. They are rebuilt with
+/// the current layout, keeping their rows.
+pub const UPGRADE_MIGRATIONS_TABLE_SQL: &str = concat!(
+    "ALTER TABLE ",
+    MIGRATIONS_TABLE_NAME,
+    " RENAME TO ",
+    MIGRATIONS_TABLE_NAME,
+    "old;
+    ",
+    CREATE_MIGRATIONS_TABLE_SQL,
+    ";
+    INSERT INTO ",
+    MIGRATIONS_TABLE_NAME,
+    " (migrated_at, id) SELECT migrated_at, id FROM ",
+    MIGRATIONS_TABLE_NAME,
+    "old ORDER BY migrated_at;
+    DROP TABLE ",
+    MIGRATIONS_TABLE_NAME,
+    "old;"
+);
+
 pub const CREATE_MIGRATIONS_TABLE_UPDATE_TRIGGER_SQL: &str = concat!(
     "CREATE TRIGGER IF NOT EXISTS update_migration_timestamp UPDATE OF id ON ",
     MIGRATIONS_TABLE_NAME,
@@ -27,14 +80,62 @@ pub const CREATE_MIGRATIONS_TABLE_UPDATE_TRIGGER_SQL: &str = concat!(
     END"
 );
 
+pub const SEEDS_TABLE_NAME: &str = "__seeds__";
+
+pub const CREATE_SEEDS_TABLE_SQL: &str = concat!(
+    "CREATE TABLE IF NOT EXISTS ",
+    SEEDS_TABLE_NAME,
+    " (
+        name TEXT PRIMARY KEY,
+        checksum TEXT NOT NULL,
+        seeded_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )"
+);
+
+/// The environment variables holding the key of SQLCipher encrypted databases, and the new key
+/// given to them by `rekey`.
+pub const MIGRATOR_DB_KEY_ENV: &str = "MIGRATOR_DB_KEY";
+pub const MIGRATOR_DB_NEW_KEY_ENV: &str = "MIGRATOR_DB_NEW_KEY";
+
+/// The configuration file, read from the working directory.
+pub const MIGRATOR_CONFIG_FILE: &str = "migrator.toml";
+
 pub const MIGRATOR_MAIN_DIR: &str = "migrations";
 pub const MIGRATOR_SQLITE_SUBDIR_BASENAME: &str = "sqlite";
+pub const MIGRATOR_POSTGRES_SUBDIR_BASENAME: &str = "postgres";
+pub const MIGRATOR_MYSQL_SUBDIR_BASENAME: &str = "mysql";
+pub const MIGRATOR_TEMPLATES_DIR_BASENAME: &str = "templates";
 pub const MIGRATOR_UP_DIR_BASENAME: &str = "up";
 pub const MIGRATOR_DOWN_DIR_BASENAME: &str = "down";
+pub const MIGRATOR_REPEATABLE_DIR_BASENAME: &str = "repeatable";
+pub const MIGRATOR_SEEDS_DIR_BASENAME: &str = "seeds";
+pub const MIGRATOR_TESTS_DIR_BASENAME: &str = "tests";
+
+pub const MIGRATOR_TEMPLATES_DIR: &str =
+    join_dirs!(MIGRATOR_MAIN_DIR, MIGRATOR_TEMPLATES_DIR_BASENAME);
 
+#[deprecated(note = "use Dialect::Sqlite.dir(), every dialect has its own subdirectory")]
 pub const MIGRATOR_SQLITE_SUBDIR: &str =
     join_dirs!(MIGRATOR_MAIN_DIR, MIGRATOR_SQLITE_SUBDIR_BASENAME);
-pub const MIGRATOR_UP_DIR: &str = join_dirs!(MIGRATOR_SQLITE_SUBDIR, MIGRATOR_UP_DIR_BASENAME);
-pub const MIGRATOR_DOWN_DIR: &str = join_dirs!(MIGRATOR_SQLITE_SUBDIR, MIGRATOR_DOWN_DIR_BASENAME);
+#[deprecated(note = "use Dialect::up_dir, every dialect has its own subdirectory")]
+pub const MIGRATOR_UP_DIR: &str = join_dirs!(
+    MIGRATOR_MAIN_DIR,
+    MIGRATOR_SQLITE_SUBDIR_BASENAME,
+    MIGRATOR_UP_DIR_BASENAME
+);
+#[deprecated(note = "use Dialect::down_dir, every dialect has its own subdirectory")]
+pub const MIGRATOR_DOWN_DIR: &str = join_dirs!(
+    MIGRATOR_MAIN_DIR,
+    MIGRATOR_SQLITE_SUBDIR_BASENAME,
+    MIGRATOR_DOWN_DIR_BASENAME
+);
 
 pub const MIGRATION_MAX_NAME_FOR_FILE: usize = 40;
+
+/// Prefix of the header comments carrying migration metadata (`-- migrator:<key> <value>`).
+pub const MIGRATION_HEADER_PREFIX: &str = "migrator:";
+pub const MIGRATION_DEPENDS_ON_HEADER: &str = "depends-on";
+pub const MIGRATION_ENV_HEADER: &str = "env";
+pub const MIGRATION_REQUIRES_EXTENSION_HEADER: &str = "requires-extension";
+
+pub const MIGRATION_MERGE_DEFAULT_NAME: &str = "merge";
@@ -0,0 +1,54 @@
+use serde::Deserialize;
+
+/// The SQL dialect of a database. Every dialect has its own migrations subdirectory, e.g.
+/// `migrations/sqlite`, as migrations are rarely portable between databases.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Dialect {
+    #[default]
+    Sqlite,
+    Postgres,
+    Mysql,
+}
+
+impl Dialect {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Dialect::Sqlite => crate::MIGRATOR_SQLITE_SUBDIR_BASENAME,
+            Dialect::Postgres => crate::MIGRATOR_POSTGRES_SUBDIR_BASENAME,
+            Dialect::Mysql => crate::MIGRATOR_MYSQL_SUBDIR_BASENAME,
+        }
+    }
+
+    /// The migrations subdirectory of the dialect.
+    pub fn dir(&self) -> String {
+        format!("{}/{}", crate::MIGRATOR_MAIN_DIR, self.as_str())
+    }
+
+    pub fn up_dir(&self) -> String {
+        format!("{}/{}", self.dir(), crate::MIGRATOR_UP_DIR_BASENAME)
+    }
+
+    pub fn down_dir(&self) -> String {
+        format!("{}/{}", self.dir(), crate::MIGRATOR_DOWN_DIR_BASENAME)
+    }
+
+    pub fn repeatable_dir(&self) -> String {
+        format!("{}/{}", self.dir(), crate::MIGRATOR_REPEATABLE_DIR_BASENAME)
+    }
+
+    pub fn seeds_dir(&self) -> String {
+        format!("{}/{}", self.dir(), crate::MIGRATOR_SEEDS_DIR_BASENAME)
+    }
+
+    /// The assertions run by `migrate test`.
+    pub fn tests_dir(&self) -> String {
+        format!("{}/{}", self.dir(), crate::MIGRATOR_TESTS_DIR_BASENAME)
+    }
+}
+
+impl std::fmt::Display for Dialect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use crate::{Migration, migrations::MigrationId};
+
+#[derive(Debug, thiserror::Error)]
+pub enum MigrationGraphError {
+    #[error("Migration id {0} is used by more than one migration")]
+    DuplicateId(String),
+
+    #[error("Migration {0} depends on unknown migration {1}")]
+    UnknownParent(String, String),
+
+    #[error("Circular dependency detected between migrations: {}", .0.join(", "))]
+    Cycle(Vec<String>),
+}
+
+/// The migrations arranged as a directed acyclic graph, where every migration points to the
+/// migrations it depends on (its parents).
+///
+/// Parents are declared with `-- migrator:depends-on <id>` headers. A migration without such a
+/// header depends on the migration preceding it by id, which keeps histories created before the
+/// headers were introduced linear.
+#[derive(Debug)]
+pub struct MigrationGraph {
+    /// The migrations in topological order. Migrations that do not depend on each other are
+    /// ordered by their id.
+    migrations: Vec<Migration>,
+    parents: Vec<Vec<usize>>,
+    children: Vec<Vec<usize>>,
+}
+
+impl MigrationGraph {
+    pub fn new(mut migrations: Vec<Migration>) -> Result<Self, MigrationGraphError> {
+        migrations.sort();
+
+        let mut positions = HashMap::with_capacity(migrations.len());
+        for (index, migration) in migrations.iter().enumerate() {
+            if positions.insert(migration.id, index).is_some() {
+                return Err(MigrationGraphError::DuplicateId(migration.stringify_id()));
+            }
+        }
+
+        let mut parents = Vec::with_capacity(migrations.len());
+        for (index, migration) in migrations.iter().enumerate() {
+            let migration_parents = if migration.depends_on().is_empty() {
+                index.checked_sub(1).into_iter().collect()
+            } else {
+                migration
+                    .depends_on()
+                    .iter()
+                    .map(|parent| {
+                        positions.get(parent).copied().ok_or_else(|| {
+                            MigrationGraphError::UnknownParent(
+                                migration.stringify_id(),
+                                parent.to_string(),
+                            )
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+            };
+
+            parents.push(migration_parents);
+        }
+
+        let order = Self::topological_order(&parents).map_err(|remaining| {
+            MigrationGraphError::Cycle(
+                remaining
+                    .into_iter()
+                    .map(|index| migrations[index].stringify_id())
+                    .collect(),
+            )
+        })?;
+
+        let mut new_positions = vec![0; order.len()];
+        for (new_index, old_index) in order.iter().enumerate() {
+            new_positions[*old_index] = new_index;
+        }
+
+        let mut slots = migrations.into_iter().map(Some).collect::<Vec<_>>();
+        let migrations = order
+            .iter()
+            .map(|old_index| slots[*old_index].take().unwrap())
+            .collect::<Vec<_>>();
+        let parents = order
+            .iter()
+            .map(|old_index| {
+                parents[*old_index]
+                    .iter()
+                    .map(|parent| new_positions[*parent])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut children = vec![Vec::new(); migrations.len()];
+        for (index, migration_parents) in parents.iter().enumerate() {
+            for parent in migration_parents {
+                children[*parent].push(index);
+            }
+        }
+
+        Ok(Self {
+            migrations,
+            parents,
+            children,
+        })
+    }
+
+    /// Kahn's algorithm, always picking the ready migration with the lowest id. Returns the
+    /// indices that could not be ordered if the graph contains a cycle.
+    fn topological_order(parents: &[Vec<usize>]) -> Result<Vec<usize>, Vec<usize>> {
+        let mut pending_parents = parents.iter().map(Vec::len).collect::<Vec<_>>();
+        let mut children = vec![Vec::new(); parents.len()];
+        for (index, migration_parents) in parents.iter().enumerate() {
+            for parent in migration_parents {
+                children[*parent].push(index);
+            }
+        }
+
+        let mut ready = pending_parents
+            .iter()
+            .enumerate()
+            .filter(|(_, pending)| **pending == 0)
+            .map(|(index, _)| Reverse(index))
+            .collect::<BinaryHeap<_>>();
+        let mut order = Vec::with_capacity(parents.len());
+
+        while let Some(Reverse(index)) = ready.pop() {
+            order.push(index);
+
+            for child in &children[index] {
+                pending_parents[*child] -= 1;
+                if pending_parents[*child] == 0 {
+                    ready.push(Reverse(*child));
+                }
+            }
+        }
+
+        if order.len() == parents.len() {
+            Ok(order)
+        } else {
+            Err((0..parents.len())
+                .filter(|index| pending_parents[*index] > 0)
+                .collect())
+        }
+    }
+
+    /// The migrations in topological order, meaning every migration comes after its parents.
+    pub fn migrations(&self) -> &[Migration] {
+        &self.migrations
+    }
+
+    pub fn len(&self) -> usize {
+        self.migrations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.migrations.is_empty()
+    }
+
+    pub fn index_of(&self, id: &MigrationId) -> Option<usize> {
+        self.migrations.iter().position(|m| m.id == *id)
+    }
+
+    pub fn get(&self, id: &MigrationId) -> Option<&Migration> {
+        self.index_of(id).map(|index| &self.migrations[index])
+    }
+
+    pub fn parents(&self, index: usize) -> &[usize] {
+        &self.parents[index]
+    }
+
+    pub fn children(&self, index: usize) -> &[usize] {
+        &self.children[index]
+    }
+
+    pub fn is_merge(&self, index: usize) -> bool {
+        self.parents[index].len() > 1
+    }
+
+    /// Migrations that do not depend on any other migration.
+    pub fn roots(&self) -> Vec<usize> {
+        (0..self.migrations.len())
+            .filter(|index| self.parents[*index].is_empty())
+            .collect()
+    }
+
+    /// Migrations that no other migration depends on.
+    pub fn heads(&self) -> Vec<usize> {
+        (0..self.migrations.len())
+            .filter(|index| self.children[*index].is_empty())
+            .collect()
+    }
+
+    /// The single head of the graph, `None` if there are no migrations at all.
+    pub fn head(&self) -> Result<Option<usize>, &'static str> {
+        match self.heads().as_slice() {
+            [] => Ok(None),
+            [head] => Ok(Some(*head)),
+            _ => Err("Multiple heads found, merge them with 'migrate merge' first"),
+        }
+    }
+
+    /// The given migration together with every migration it depends on, directly or indirectly.
+    pub fn ancestors(&self, index: usize) -> HashSet<usize> {
+        let mut ancestors = HashSet::new();
+        let mut stack = vec![index];
+
+        while let Some(index) = stack.pop() {
+            if ancestors.insert(index) {
+                stack.extend(self.parents[index].iter().copied());
+            }
+        }
+
+        ancestors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migration(id: &str, depends_on: &[&str]) -> Migration {
+        Migration::new(MigrationId::try_from(id).unwrap(), format!("m{}", id)).with_depends_on(
+            depends_on
+                .iter()
+                .map(|parent| MigrationId::try_from(*parent).unwrap())
+                .collect(),
+        )
+    }
+
+    fn ids(graph: &MigrationGraph, indices: impl IntoIterator<Item = usize>) -> Vec<String> {
+        let mut ids = indices
+            .into_iter()
+            .map(|index| graph.migrations()[index].stringify_id())
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn orders_implicit_and_declared_parents() {
+        // 2 declares a parent created after it, 3 implicitly depends on 1.
+        let graph = MigrationGraph::new(vec![
+            migration("2", &["3"]),
+            migration("1", &[]),
+            migration("3", &["1"]),
+            migration("4", &[]),
+        ])
+        .unwrap();
+
+        let order = graph
+            .migrations()
+            .iter()
+            .map(Migration::stringify_id)
+            .collect::<Vec<_>>();
+        assert_eq!(order, ["1", "3", "2", "4"]);
+
+        let index = |id: &str| graph.index_of(&MigrationId::try_from(id).unwrap()).unwrap();
+        assert_eq!(ids(&graph, graph.parents(index("2")).to_vec()), ["3"]);
+        assert_eq!(ids(&graph, graph.parents(index("4")).to_vec()), ["3"]);
+        assert_eq!(ids(&graph, graph.roots()), ["1"]);
+        assert_eq!(ids(&graph, graph.heads()), ["2", "4"]);
+        assert!(graph.head().is_err());
+    }
+
+    #[test]
+    fn detects_cycles() {
+        // 3 implicitly depends on 2, which depends on 3.
+        let err = MigrationGraph::new(vec![
+            migration("1", &[]),
+            migration("2", &["3"]),
+            migration("3", &[]),
+        ])
+        .unwrap_err();
+
+        assert!(matches!(&err, MigrationGraphError::Cycle(ids) if ids == &["2", "3"]));
+    }
+
+    #[test]
+    fn rejects_unknown_parents_and_duplicate_ids() {
+        let err =
+            MigrationGraph::new(vec![migration("1", &[]), migration("2", &["9"])]).unwrap_err();
+        assert!(
+            matches!(&err, MigrationGraphError::UnknownParent(id, parent) if id == "2" && parent == "9")
+        );
+
+        let err = MigrationGraph::new(vec![migration("1", &[]), migration("1", &[])]).unwrap_err();
+        assert!(matches!(&err, MigrationGraphError::DuplicateId(id) if id == "1"));
+    }
+
+    #[test]
+    fn ancestors_across_a_merge() {
+        // 1 <- 2 and 1 <- 3 on parallel branches, merged by 4.
+        let graph = MigrationGraph::new(vec![
+            migration("1", &[]),
+            migration("2", &[]),
+            migration("3", &["1"]),
+            migration("4", &["2", "3"]),
+        ])
+        .unwrap();
+        let index = |id: &str| graph.index_of(&MigrationId::try_from(id).unwrap()).unwrap();
+
+        assert!(graph.is_merge(index("4")));
+        assert_eq!(graph.head(), Ok(Some(index("4"))));
+        assert_eq!(
+            ids(&graph, graph.ancestors(index("4"))),
+            ["1", "2", "3", "4"]
+        );
+        assert_eq!(ids(&graph, graph.ancestors(index("3"))), ["1", "3"]);
+        assert_eq!(ids(&graph, graph.ancestors(index("2"))), ["1", "2"]);
+        assert_eq!(ids(&graph, graph.children(index("1")).to_vec()), ["2", "3"]);
+    }
+}
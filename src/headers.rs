@@ -0,0 +1,98 @@
+/// Metadata attached to a migration file through header comments at the top of the file, e.g.
+/// `-- migrator:depends-on 20250101000000`.
+#[derive(Debug, Default, Clone)]
+pub struct MigrationHeaders {
+    entries: Vec<(String, String)>,
+}
+
+impl MigrationHeaders {
+    /// Parses the header comments of the given SQL. Only the leading block of comments and blank
+    /// lines is considered, so headers must come before the first statement.
+    pub fn parse(sql: &str) -> Self {
+        let mut entries = Vec::new();
+
+        for line in sql.lines() {
+            let line = line.trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let Some(comment) = line.strip_prefix("--") else {
+                break;
+            };
+
+            if let Some(header) = comment
+                .trim_start()
+                .strip_prefix(crate::MIGRATION_HEADER_PREFIX)
+            {
+                let (key, value) = header
+                    .trim()
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((header.trim(), ""));
+                entries.push((key.to_lowercase(), value.trim().to_string()));
+            }
+        }
+
+        Self { entries }
+    }
+
+    /// Returns every value given for `key`. Values can be spread across several header lines and
+    /// can be separated by commas or whitespace.
+    pub fn values<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .flat_map(|(_, value)| value.split(|c: char| c == ',' || c.is_whitespace()))
+            .filter(|value| !value.is_empty())
+    }
+
+    /// Renders a single header line for `key` with the given values.
+    pub fn render<T: std::fmt::Display>(key: &str, values: &[T]) -> String {
+        let values = values.iter().map(T::to_string).collect::<Vec<_>>();
+        format!(
+            "-- {}{} {}\n",
+            crate::MIGRATION_HEADER_PREFIX,
+            key,
+            values.join(", ")
+        )
+    }
+
+    /// Replaces the header lines for `key` at the top of the SQL with a single line holding the
+    /// given values, or removes them if there are no values. The rest of the SQL is kept as is.
+    pub fn replace<T: std::fmt::Display>(sql: &str, key: &str, values: &[T]) -> String {
+        let mut result = String::with_capacity(sql.len());
+        let mut replaced = values.is_empty();
+        let mut in_header = true;
+
+        for line in sql.split_inclusive('\n') {
+            let trimmed = line.trim();
+
+            if in_header && !trimmed.is_empty() && !trimmed.starts_with("--") {
+                in_header = false;
+            }
+
+            let header_key = trimmed
+                .strip_prefix("--")
+                .map(str::trim_start)
+                .and_then(|comment| comment.strip_prefix(crate::MIGRATION_HEADER_PREFIX))
+                .and_then(|header| header.split_whitespace().next());
+
+            if in_header && header_key.is_some_and(|k| k.eq_ignore_ascii_case(key)) {
+                if !replaced {
+                    result.push_str(&Self::render(key, values));
+                    replaced = true;
+                }
+                continue;
+            }
+
+            result.push_str(line);
+        }
+
+        if replaced {
+            result
+        } else {
+            Self::render(key, values) + &result
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use serde::Deserialize;
+
+use crate::{AnyResult, MigrationId};
+
+const TIMESTAMP_FORMAT: &str = "%Y%m%d%H%M%S";
+const TIMESTAMP_MILLIS_FORMAT: &str = "%Y%m%d%H%M%S%3f";
+
+/// How the ids of new migrations are generated, set with the `ids.scheme` key of the
+/// configuration file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdScheme {
+    /// The current UTC time as `YYYYMMDDHHMMSS`.
+    #[default]
+    Timestamp,
+
+    /// The current UTC time with milliseconds as `YYYYMMDDHHMMSSmmm`.
+    TimestampMillis,
+
+    /// Zero-padded integers counting up from 1, e.g. `0001`.
+    Sequential,
+}
+
+impl std::fmt::Display for IdScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            IdScheme::Timestamp => "timestamp",
+            IdScheme::TimestampMillis => "timestamp-millis",
+            IdScheme::Sequential => "sequential",
+        })
+    }
+}
+
+impl IdScheme {
+    /// Generates the id of a new migration, making sure it sorts after every `existing` id. If the
+    /// current time is already taken, the timestamp following the latest existing id is used, so
+    /// migrations created in quick succession still get distinct ids. Sequential ids are padded
+    /// with zeros to `width` digits.
+    pub fn generate<'a>(
+        &self,
+        existing: impl IntoIterator<Item = &'a MigrationId>,
+        width: usize,
+    ) -> AnyResult<MigrationId> {
+        let latest = existing.into_iter().max();
+
+        let id = match self {
+            IdScheme::Timestamp | IdScheme::TimestampMillis => {
+                let (format, step) = match self {
+                    IdScheme::Timestamp => (TIMESTAMP_FORMAT, TimeDelta::seconds(1)),
+                    _ => (TIMESTAMP_MILLIS_FORMAT, TimeDelta::milliseconds(1)),
+                };
+
+                let now = Utc::now().naive_utc();
+                let mut id = MigrationId::try_from(now.format(format).to_string().as_str())?;
+
+                if let Some(latest) = latest
+                    && *latest >= id
+                {
+                    let latest_time = Self::parse_timestamp(latest).ok_or_else(|| {
+                        format!(
+                            "Cannot generate a timestamp id after migration {}, which is not a timestamp",
+                            latest
+                        )
+                    })?;
+                    let next = (latest_time + step).format(format).to_string();
+                    id = MigrationId::try_from(next.as_str())?;
+                }
+
+                // Longer ids sort after shorter ones, so after switching from millisecond to
+                // second timestamps no new id can sort after the millisecond ones.
+                if let Some(latest) = latest
+                    && *latest >= id
+                {
+                    return Err(format!(
+                        "Cannot generate a {} id sorting after migration {}, whose id is longer. \
+                         Keep the scheme the latest migrations were created with",
+                        self, latest
+                    )
+                    .into());
+                }
+
+                id
+            }
+            IdScheme::Sequential => {
+                let next = match latest {
+                    Some(latest) => latest
+                        .as_str()
+                        .parse::<u64>()
+                        .map_err(|err| format!("Invalid migration id {}: {}", latest, err))?
+                        .checked_add(1)
+                        .ok_or("Migration ids are exhausted")?,
+                    None => 1,
+                };
+
+                MigrationId::try_from(format!("{:0width$}", next, width = width).as_str())?
+            }
+        };
+
+        Ok(id)
+    }
+
+    /// The time encoded in a timestamp id, `None` for ids of other schemes.
+    pub fn parse_timestamp(id: &MigrationId) -> Option<NaiveDateTime> {
+        let format = match id.len() {
+            14 => TIMESTAMP_FORMAT,
+            17 => TIMESTAMP_MILLIS_FORMAT,
+            _ => return None,
+        };
+
+        NaiveDateTime::parse_from_str(id.as_str(), format).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(ids: &[&str]) -> Vec<MigrationId> {
+        ids.iter()
+            .map(|id| MigrationId::try_from(*id).unwrap())
+            .collect()
+    }
+
+    fn generate(scheme: IdScheme, existing: &[&str]) -> Result<String, String> {
+        scheme
+            .generate(&ids(existing), 4)
+            .map(|id| id.to_string())
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn generates_timestamps() {
+        let id = generate(IdScheme::Timestamp, &["20000101000000"]).unwrap();
+        assert_eq!(id.len(), 14);
+        assert!(IdScheme::parse_timestamp(&ids(&[&id])[0]).is_some());
+
+        let id = generate(IdScheme::TimestampMillis, &["20000101000000000"]).unwrap();
+        assert_eq!(id.len(), 17);
+        assert!(IdScheme::parse_timestamp(&ids(&[&id])[0]).is_some());
+    }
+
+    #[test]
+    fn generates_timestamps_after_later_ids() {
+        assert_eq!(
+            generate(IdScheme::Timestamp, &["20000101000000", "30000101235959"]).unwrap(),
+            "30000102000000"
+        );
+        assert_eq!(
+            generate(IdScheme::TimestampMillis, &["30000101235959999"]).unwrap(),
+            "30000102000000000"
+        );
+        assert!(generate(IdScheme::Timestamp, &["300001010000000000"]).is_err());
+    }
+
+    #[test]
+    fn generates_sequential_ids() {
+        assert_eq!(generate(IdScheme::Sequential, &[]).unwrap(), "0001");
+        assert_eq!(
+            generate(IdScheme::Sequential, &["0001", "0009"]).unwrap(),
+            "0010"
+        );
+        assert_eq!(generate(IdScheme::Sequential, &["9999"]).unwrap(), "10000");
+        assert_eq!(
+            generate(IdScheme::Sequential, &["18446744073709551615"]).unwrap_err(),
+            "Migration ids are exhausted"
+        );
+    }
+
+    #[test]
+    fn switching_schemes_keeps_ids_ordered() {
+        // Second to millisecond timestamps, and timestamps to sequential ids.
+        let id = generate(IdScheme::TimestampMillis, &["20000101000000"]).unwrap();
+        assert!(ids(&[&id])[0] > ids(&["20000101000000"])[0]);
+        assert_eq!(
+            generate(IdScheme::Sequential, &["20000101000000"]).unwrap(),
+            "20000101000001"
+        );
+
+        // Sequential to timestamp ids.
+        let id = generate(IdScheme::Timestamp, &["0042"]).unwrap();
+        assert_eq!(id.len(), 14);
+
+        // No second timestamp sorts after a millisecond timestamp.
+        assert_eq!(
+            generate(IdScheme::Timestamp, &["20000101000000000"]).unwrap_err(),
+            "Cannot generate a timestamp id sorting after migration 20000101000000000, whose id \
+             is longer. Keep the scheme the latest migrations were created with"
+        );
+    }
+}
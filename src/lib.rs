@@ -1,16 +1,61 @@
 mod any_error;
+mod backend;
+mod check;
 pub mod cli;
 pub mod commands;
+mod config;
 mod constants;
+mod dialect;
+mod graph;
+mod headers;
+mod id_scheme;
 mod migrations;
+mod migrator;
+mod names;
+mod plan;
+mod progress;
+mod repeatable;
 mod revision;
+mod rust_migration;
+mod seeds;
+mod statements;
+mod status;
+mod templates;
 pub mod traits;
 mod utils;
+mod variables;
 
 pub use any_error::*;
+pub use backend::{
+    Backend, MysqlBackend, PostgresBackend, SqliteBackend, SqliteMode, SqliteUrl, column_exists,
+    table_exists,
+};
+pub use check::{CheckIssue, CheckIssueKind, PendingMigrations, check_migrations, touched_tables};
+pub use config::{Config, EnvConfig, IdsConfig, SqliteConfig};
 pub use constants::*;
-pub use migrations::{Migration, get_current_migration_id, get_migration_history};
-pub use revision::{RelativeRevision, Revision};
-pub use utils::{
-    create_migration_table, create_migrations_dir, run_with_transaction, table_exists,
+pub use dialect::Dialect;
+pub use graph::{MigrationGraph, MigrationGraphError};
+pub use headers::MigrationHeaders;
+pub use id_scheme::IdScheme;
+pub use migrations::{
+    InvalidMigrationIdError, Migration, MigrationId, MigrationKind, get_current_migration_id,
+    get_migration_history, get_skipped_migration_ids, split_migration_stem,
 };
+pub use migrator::Migrator;
+pub use names::{NamesConfig, UnicodeNames};
+pub use plan::{MigrationDirection, MigrationPlan, MigrationPlanStep};
+pub use progress::MigrationProgress;
+pub use repeatable::{
+    RepeatableMigration, get_pending_repeatable_migrations, get_repeatable_migrations,
+};
+pub use revision::{RelativeRevision, Revision};
+/// The SQLite driver, for Rust migrations using the connection and the SQL functions registered
+/// with the [`Migrator`].
+pub use rusqlite;
+pub use rust_migration::RustMigration;
+pub use seeds::{Seed, get_seeds};
+pub use statements::split_statements;
+pub use status::{MigrationState, MigrationStatus, MigrationStatusEntry};
+pub use templates::MigrationTemplate;
+pub use utils::{checksum, create_migrations_dir, run_with_transaction};
+pub use variables::{Variables, parse_variable};
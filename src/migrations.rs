@@ -1,85 +1,235 @@
-use std::{array::TryFromSliceError, collections::HashSet, fs::DirEntry};
+use std::{collections::HashSet, sync::Arc};
+
+use crate::{
+    AnyResult, Backend, Dialect, MigrationDirection, RustMigration, Variables,
+    graph::MigrationGraph, headers::MigrationHeaders,
+};
+
+/// The id of a migration: the leading digits of its filename, e.g. a `YYYYMMDDHHMMSS` timestamp or
+/// a zero-padded sequence number, depending on the [`crate::id_scheme::IdScheme`] of the project.
+///
+/// Ids are ordered numerically, so ids of different lengths, e.g. after switching from second to
+/// millisecond timestamps, keep the order the migrations were created in.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MigrationId {
+    bytes: [u8; MigrationId::MAX_LEN],
+    len: u8,
+}
 
-use rusqlite::Transaction;
+impl MigrationId {
+    /// The maximum number of digits of an id, enough for any `u64`.
+    pub const MAX_LEN: usize = 20;
 
-use crate::AnyResult;
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
-pub struct MigrationId([u8; 14]);
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(self.as_bytes()).unwrap()
+    }
 
-impl MigrationId {
-    pub fn new(id: [u8; 14]) -> Self {
-        Self(id)
+    pub fn len(&self) -> usize {
+        self.len as usize
     }
 
-    pub fn as_bytes(&self) -> &[u8; 14] {
-        &self.0
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
+}
 
-    pub fn as_str(&self) -> &str {
-        std::str::from_utf8(&self.0).unwrap()
+impl Ord for MigrationId {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.len
+            .cmp(&other.len)
+            .then_with(|| self.as_bytes().cmp(other.as_bytes()))
+    }
+}
+
+impl PartialOrd for MigrationId {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid migration id '{0}', expected 1 to {max} digits", max = MigrationId::MAX_LEN)]
+pub struct InvalidMigrationIdError(String);
+
 impl std::fmt::Debug for MigrationId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(&format!("MigrationId({})", self.as_str()))
     }
 }
 
-impl ToString for MigrationId {
-    fn to_string(&self) -> String {
-        std::str::from_utf8(&self.0).unwrap().to_string()
+impl std::fmt::Display for MigrationId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl serde::Serialize for MigrationId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
     }
 }
 
 impl TryFrom<&str> for MigrationId {
-    type Error = TryFromSliceError;
+    type Error = InvalidMigrationIdError;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        Ok(Self(value.as_bytes().try_into()?))
+        Self::try_from(value.as_bytes())
     }
 }
 
 impl TryFrom<&[u8]> for MigrationId {
-    type Error = TryFromSliceError;
+    type Error = InvalidMigrationIdError;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        Ok(Self(value.try_into()?))
+        if value.is_empty() || value.len() > Self::MAX_LEN || !value.iter().all(u8::is_ascii_digit)
+        {
+            return Err(InvalidMigrationIdError(
+                String::from_utf8_lossy(value).to_string(),
+            ));
+        }
+
+        let mut bytes = [0; Self::MAX_LEN];
+        bytes[..value.len()].copy_from_slice(value);
+
+        Ok(Self {
+            bytes,
+            len: value.len() as u8,
+        })
+    }
+}
+
+/// Splits the name of a migration file without its extension (`<id>_<name>`) into the id and the
+/// name of the migration.
+pub fn split_migration_stem(stem: &str) -> Option<(MigrationId, &str)> {
+    let (id, name) = stem.split_once('_')?;
+
+    if name.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
     }
+
+    Some((MigrationId::try_from(id).ok()?, name))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationKind {
+    /// The migration is a pair of SQL files in the migrations directory.
+    Sql,
+
+    /// The migration is a [`RustMigration`] registered with the migrator.
+    Rust,
 }
 
 /// Defines a migration. A migration is a set of SQL queries that are executed in order to update
 /// the database schema.
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
 pub struct Migration {
-    /// The unique identifier of the migration. Defined by the current datetime in the format
-    /// `YYYYMMDDHHMMSS`.
+    /// The unique identifier of the migration, generated by the [`crate::IdScheme`] of the
+    /// project. By default the current datetime in the format `YYYYMMDDHHMMSS`.
     pub id: MigrationId,
 
     /// The name of the migration. This is the name that will be displayed in the migration table.
     name: String,
+
+    /// The migrations this migration explicitly depends on, declared with
+    /// `-- migrator:depends-on` headers. Empty if the migration implicitly depends on the
+    /// migration preceding it.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    depends_on: Vec<MigrationId>,
+
+    /// The environments the migration is restricted to, declared with a `-- migrator:env` header.
+    /// Empty if the migration runs in every environment.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    environments: Vec<String>,
+
+    /// The SQLite extensions loaded before the migration runs, declared with
+    /// `-- migrator:requires-extension` headers.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    extensions: Vec<String>,
+
+    /// The dialect whose migrations subdirectory holds the files of the migration.
+    #[serde(skip)]
+    dialect: Dialect,
+
+    kind: MigrationKind,
+
+    /// The code of the migration if it is a Rust migration.
+    #[serde(skip)]
+    code: Option<Arc<dyn RustMigration>>,
 }
 
 impl Migration {
-    pub fn new(name: impl AsRef<str>) -> Result<Self, TryFromSliceError> {
-        let id = chrono::Utc::now().format("%Y%m%d%H%M%S").to_string();
-        let id = id.as_bytes().try_into()?;
+    /// A new SQL migration. Its id is usually generated with [`crate::IdScheme::generate`].
+    pub fn new(id: MigrationId, name: impl AsRef<str>) -> Self {
+        Self {
+            id,
+            name: name.as_ref().to_string().replace(" ", "_"),
+            depends_on: Vec::new(),
+            environments: Vec::new(),
+            extensions: Vec::new(),
+            dialect: Dialect::default(),
+            kind: MigrationKind::Sql,
+            code: None,
+        }
+    }
+
+    pub fn from_rust(code: Arc<dyn RustMigration>) -> AnyResult<Self> {
+        let invalid_id = |id: &str| format!("Rust migration {:?} has an invalid id: {}", code, id);
+
+        let id = MigrationId::try_from(code.id()).map_err(|_| invalid_id(code.id()))?;
+        let depends_on = code
+            .depends_on()
+            .iter()
+            .map(|parent| MigrationId::try_from(parent.as_str()).map_err(|_| invalid_id(parent)))
+            .collect::<Result<Vec<_>, _>>()?;
 
         Ok(Self {
             id,
-            name: name.as_ref().to_string().replace(" ", "_"),
+            name: code.name().to_string(),
+            depends_on,
+            environments: code.environments(),
+            extensions: Vec::new(),
+            dialect: code.dialect(),
+            kind: MigrationKind::Rust,
+            code: Some(code),
         })
     }
 
-    pub fn from_filename(filename: impl AsRef<str>) -> Result<Self, TryFromSliceError> {
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    pub fn with_depends_on(mut self, depends_on: Vec<MigrationId>) -> Self {
+        self.depends_on = depends_on;
+        self
+    }
+
+    /// The migration of a `<id>_<name>.sql` file, the extension being optional.
+    pub fn from_filename(filename: impl AsRef<str>) -> Result<Self, InvalidMigrationIdError> {
         let filename = filename.as_ref();
-        let wow = filename.splitn(2, '_').collect::<Vec<_>>();
-        let (id, name) = (wow[0], wow[1].replace(".sql", ""));
+        let stem = filename.strip_suffix(".sql").unwrap_or(filename);
+        let (id, name) = stem
+            .split_once('_')
+            .ok_or_else(|| InvalidMigrationIdError(stem.to_string()))?;
 
         Ok(Self {
-            id: id.as_bytes().try_into()?,
+            id: MigrationId::try_from(id)?,
             name: name.to_string(),
+            depends_on: Vec::new(),
+            environments: Vec::new(),
+            extensions: Vec::new(),
+            dialect: Dialect::default(),
+            kind: MigrationKind::Sql,
+            code: None,
         })
     }
 
@@ -87,8 +237,47 @@ impl Migration {
         &self.name
     }
 
-    pub fn created_at(&self) -> chrono::NaiveDateTime {
-        chrono::NaiveDateTime::parse_from_str(self.id.as_str(), "%Y%m%d%H%M%S").unwrap()
+    pub fn depends_on(&self) -> &[MigrationId] {
+        &self.depends_on
+    }
+
+    pub fn environments(&self) -> &[String] {
+        &self.environments
+    }
+
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    /// Loads the extensions the migration requires into the connection.
+    pub fn load_extensions(&self, backend: &mut dyn Backend) -> AnyResult<()> {
+        for extension in self.extensions.iter() {
+            backend.load_extension(extension).map_err(|err| {
+                format!(
+                    "Migration {} requires extension {}\n{}",
+                    self.id,
+                    extension,
+                    err.to_string()
+                )
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the migration runs in the given environment. Migrations restricted to some
+    /// environments are skipped when no environment is given.
+    pub fn applies_to(&self, env: Option<&str>) -> bool {
+        crate::utils::runs_in_env(&self.environments, env)
+    }
+
+    pub fn kind(&self) -> MigrationKind {
+        self.kind
+    }
+
+    /// The creation time of the migration, `None` if its id is not a timestamp.
+    pub fn created_at(&self) -> Option<chrono::NaiveDateTime> {
+        crate::IdScheme::parse_timestamp(&self.id)
     }
 
     pub fn stringify_id(&self) -> String {
@@ -106,39 +295,100 @@ impl Migration {
         format!("{}_{}.sql", id, name)
     }
 
-    pub fn generate_files(&self) {
-        let filename = self.generate_filename();
+    pub fn up_path(&self) -> String {
+        format!("{}/{}", self.dialect.up_dir(), self.generate_filename())
+    }
+
+    pub fn down_path(&self) -> String {
+        format!("{}/{}", self.dialect.down_dir(), self.generate_filename())
+    }
 
-        let up_path = format!("{}/{}", crate::MIGRATOR_UP_DIR, &filename);
-        let down_path = format!("{}/{}", crate::MIGRATOR_DOWN_DIR, &filename);
+    /// Writes the up and down files of the migration with the given SQL and returns their paths.
+    /// The up file starts with the headers of the migration. Existing files are never
+    /// overwritten, and no file is left behind if either cannot be written.
+    pub fn generate_files(&self, up_sql: &str, down_sql: &str) -> AnyResult<Vec<String>> {
+        if self.name.is_empty() || self.name.contains(['/', '\\']) || self.name.starts_with('.') {
+            return Err(format!("Invalid migration name: {:?}", self.name).into());
+        }
+
+        let up_path = self.up_path();
+        let down_path = self.down_path();
 
-        std::fs::write(&up_path, "").unwrap();
+        for path in [&up_path, &down_path] {
+            if std::path::Path::new(path).exists() {
+                return Err(format!("Refusing to overwrite existing file: {}", path).into());
+            }
+        }
+
+        let mut up_content = if self.depends_on.is_empty() {
+            String::new()
+        } else {
+            MigrationHeaders::render(crate::MIGRATION_DEPENDS_ON_HEADER, &self.depends_on)
+        };
+        up_content.push_str(up_sql);
+
+        crate::utils::write_new_file(&up_path, &up_content)?;
         log::debug!("Generated file: {}", up_path);
 
-        std::fs::write(&down_path, "").unwrap();
+        if let Err(err) = crate::utils::write_new_file(&down_path, down_sql) {
+            let _ = std::fs::remove_file(&up_path);
+            return Err(err);
+        }
         log::debug!("Generated file: {}", down_path);
+
+        Ok(vec![up_path, down_path])
     }
 
-    fn execute_file(&self, conn: &rusqlite::Connection, filepath: &str) -> AnyResult<()> {
-        log::debug!("Executing file: {}", filepath);
+    /// The SQL executed in the given direction, with its variables substituted. `None` for Rust
+    /// migrations.
+    pub fn sql(
+        &self,
+        direction: MigrationDirection,
+        variables: &Variables,
+    ) -> AnyResult<Option<String>> {
+        if self.code.is_some() {
+            return Ok(None);
+        }
+
+        let filepath = match direction {
+            MigrationDirection::Up => self.up_path(),
+            MigrationDirection::Down => self.down_path(),
+        };
         let sql = std::fs::read_to_string(&filepath)?;
-        conn.execute_batch(&sql)?;
+        let sql = variables
+            .substitute(&sql)
+            .map_err(|err| format!("{} in {}", err, filepath))?;
+
+        Ok(Some(sql))
+    }
+
+    fn execute_file(
+        &self,
+        backend: &mut dyn Backend,
+        direction: MigrationDirection,
+        variables: &Variables,
+    ) -> AnyResult<()> {
+        log::debug!("Executing {:?} file of migration: {}", direction, self.id);
+        self.load_extensions(backend)?;
+        if let Some(sql) = self.sql(direction, variables)? {
+            backend.execute_batch(&sql)?;
+        }
 
         Ok(())
     }
 
-    pub fn up(&self, transaction: &Transaction<'_>) -> AnyResult<()> {
-        self.execute_file(
-            transaction,
-            &format!("{}/{}", crate::MIGRATOR_UP_DIR, self.generate_filename()),
-        )
+    pub fn up(&self, backend: &mut dyn Backend, variables: &Variables) -> AnyResult<()> {
+        match &self.code {
+            Some(code) => code.up(backend),
+            None => self.execute_file(backend, MigrationDirection::Up, variables),
+        }
     }
 
-    pub fn down(&self, conn: &rusqlite::Connection) -> AnyResult<()> {
-        self.execute_file(
-            conn,
-            &format!("{}/{}", crate::MIGRATOR_DOWN_DIR, self.generate_filename()),
-        )
+    pub fn down(&self, backend: &mut dyn Backend, variables: &Variables) -> AnyResult<()> {
+        match &self.code {
+            Some(code) => code.down(backend),
+            None => self.execute_file(backend, MigrationDirection::Down, variables),
+        }
     }
 }
 
@@ -152,7 +402,7 @@ impl Eq for Migration {}
 
 impl PartialOrd for Migration {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.id.partial_cmp(&other.id)
+        Some(self.cmp(other))
     }
 }
 
@@ -192,74 +442,101 @@ impl std::fmt::Display for InconsistentMigrationsError {
     }
 }
 
-pub fn get_migration_history() -> AnyResult<Vec<Migration>> {
-    let up_files = std::fs::read_dir(crate::MIGRATOR_UP_DIR)?;
-    let down_files = std::fs::read_dir(crate::MIGRATOR_DOWN_DIR)?;
-
-    let filename_mapper = |entry: std::io::Result<DirEntry>| -> String {
-        entry
-            .unwrap()
-            .file_name()
-            .into_string()
-            .unwrap()
-            .replace(".sql", "")
+/// Reads the migrations of the dialect from the migrations directory and arranges them into a
+/// graph.
+pub fn get_migration_history(dialect: Dialect) -> AnyResult<MigrationGraph> {
+    Ok(MigrationGraph::new(read_migration_files(dialect)?)?)
+}
+
+/// Reads the SQL migrations of the dialect from the migrations directory.
+pub(crate) fn read_migration_files(dialect: Dialect) -> AnyResult<Vec<Migration>> {
+    let stems = |dir: String| -> AnyResult<HashSet<String>> {
+        let mut stems = HashSet::new();
+        for entry in std::fs::read_dir(&dir)? {
+            stems.extend(crate::utils::sql_file_stem(&dir, &entry?));
+        }
+
+        Ok(stems)
     };
 
-    let up_files: HashSet<String> = up_files.map(filename_mapper).collect();
-    let down_files: HashSet<String> = down_files.map(filename_mapper).collect();
+    let up_files = stems(dialect.up_dir())?;
+    let down_files = stems(dialect.down_dir())?;
 
     let only_up_files: HashSet<String> = up_files.difference(&down_files).cloned().collect();
     let only_down_files: HashSet<String> = down_files.difference(&up_files).cloned().collect();
 
     if !only_up_files.is_empty() || !only_down_files.is_empty() {
-        return Err(InconsistentMigrationsError {
+        Err(InconsistentMigrationsError {
             up: only_up_files,
             down: only_down_files,
         })?;
     }
 
-    let mut migrations = up_files
+    let migrations = up_files
         .iter()
         .map(|filename| {
-            let wow = filename.splitn(2, '_').collect::<Vec<_>>();
-            let (id, name) = (wow[0], wow[1]);
+            let (id, name) = split_migration_stem(filename)
+                .ok_or_else(|| format!("Invalid migration filename: {}.sql", filename))?;
 
-            Migration {
-                id: id.as_bytes().try_into().unwrap(),
+            let mut migration = Migration {
+                id,
                 name: name.to_string(),
-            }
+                depends_on: Vec::new(),
+                environments: Vec::new(),
+                extensions: Vec::new(),
+                dialect,
+                kind: MigrationKind::Sql,
+                code: None,
+            };
+
+            let sql = std::fs::read_to_string(migration.up_path())?;
+            let headers = MigrationHeaders::parse(&sql);
+            migration.environments = headers
+                .values(crate::MIGRATION_ENV_HEADER)
+                .map(str::to_string)
+                .collect();
+            migration.extensions = headers
+                .values(crate::MIGRATION_REQUIRES_EXTENSION_HEADER)
+                .map(str::to_string)
+                .collect();
+            migration.depends_on = headers
+                .values(crate::MIGRATION_DEPENDS_ON_HEADER)
+                .map(|parent| {
+                    MigrationId::try_from(parent).map_err(|_| {
+                        format!(
+                            "Migration {} has an invalid depends-on header: {}",
+                            migration.stringify_id(),
+                            parent
+                        )
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(migration)
         })
-        .collect::<Vec<_>>();
-
-    migrations.sort();
+        .collect::<AnyResult<Vec<_>>>()?;
 
     Ok(migrations)
 }
 
-pub fn get_current_migration_id(transaction: &Transaction<'_>) -> AnyResult<Option<MigrationId>> {
-    match transaction.query_row(
-        &format!(
-            "SELECT id FROM {} ORDER BY migrated_at DESC LIMIT 1",
-            crate::MIGRATIONS_TABLE_NAME
-        ),
-        [],
-        |row| {
-            let id: String = row.get(0)?;
-            let id =
-                MigrationId::try_from(id.as_str()).map_err(|_| rusqlite::Error::InvalidQuery)?;
-            Ok(Some(id))
-        },
-    ) {
-        Ok(id) => Ok(id),
-        Err(rusqlite::Error::SqliteFailure(e, msg)) => {
-            if let Some(ref m) = msg {
-                if m.contains("no such table") {
-                    return Ok(None);
-                }
-            }
-            Err(rusqlite::Error::SqliteFailure(e, msg))?
+/// The ids of the migrations recorded as skipped because they are restricted to environments
+/// other than the one they were applied in.
+pub fn get_skipped_migration_ids(backend: &mut dyn Backend) -> AnyResult<HashSet<MigrationId>> {
+    Ok(backend
+        .migration_records(crate::MIGRATION_KIND_SKIPPED)?
+        .iter()
+        .filter_map(|(id, _)| MigrationId::try_from(id.as_str()).ok())
+        .collect())
+}
+
+/// The migration the database is at, `None` if no migration is applied.
+pub fn get_current_migration_id(backend: &mut dyn Backend) -> AnyResult<Option<MigrationId>> {
+    match backend.current_revision()? {
+        Some(id) if id != crate::MIGRATION_BASE_REVISION => {
+            Ok(Some(MigrationId::try_from(id.as_str()).map_err(|err| {
+                format!("Invalid current migration in the database: {}", err)
+            })?))
         }
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(err) => Err(err)?,
+        _ => Ok(None),
     }
 }
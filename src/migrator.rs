@@ -0,0 +1,412 @@
+use std::{
+    panic::{RefUnwindSafe, UnwindSafe},
+    sync::Arc,
+};
+
+use clap::Parser;
+use rusqlite::{
+    Connection,
+    functions::{Aggregate, Context, FunctionFlags, SqlFnOutput},
+};
+
+use crate::{
+    AnyResult, Backend, Config, Dialect, Migration, MigrationGraph, Revision, RustMigration,
+    SqliteBackend, Variables,
+    cli::{Cli, Commands, MigrateCommands},
+    commands::*,
+    migrations::read_migration_files,
+    run_with_transaction,
+    traits::ExitIfError,
+};
+
+/// The entry point of the migrator when used as a library. It holds the migrations registered in
+/// code next to the SQL files of the migrations directory, and runs the commands of the command
+/// line interface with them.
+///
+/// ```no_run
+/// migrator::Migrator::new()
+///     // .register(MyRustMigration)
+///     .run_cli();
+/// ```
+#[derive(Default)]
+pub struct Migrator {
+    rust_migrations: Vec<Arc<dyn RustMigration>>,
+
+    /// The SQL functions created on SQLite connections, by name.
+    sqlite_functions: Vec<(String, SqliteFunction)>,
+}
+
+/// Creates a SQL function on a SQLite connection.
+type SqliteFunction = Arc<dyn Fn(&Connection) -> rusqlite::Result<()> + Send + Sync>;
+
+impl Migrator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a migration written in Rust. It is ordered together with the SQL migrations by
+    /// its id and recorded in the same migrations table.
+    pub fn register(mut self, migration: impl RustMigration + 'static) -> Self {
+        self.rust_migrations.push(Arc::new(migration));
+        self
+    }
+
+    /// Registers a scalar SQL function, created on SQLite connections before any migration or
+    /// seed runs, e.g. so that a data migration can `UPDATE posts SET slug = app_slugify(title)`.
+    /// `n_arg` is the number of arguments, -1 for any. See
+    /// [`Connection::create_scalar_function`].
+    pub fn scalar_function<F, T>(
+        mut self,
+        name: &str,
+        n_arg: i32,
+        flags: FunctionFlags,
+        function: F,
+    ) -> Self
+    where
+        F: Fn(&Context<'_>) -> rusqlite::Result<T> + Send + Sync + 'static,
+        T: SqlFnOutput,
+    {
+        let function = Arc::new(function);
+        let function_name = name.to_string();
+        let create = move |connection: &Connection| {
+            let function = function.clone();
+            connection.create_scalar_function(&function_name, n_arg, flags, move |context| {
+                function(context)
+            })
+        };
+
+        self.sqlite_functions
+            .push((name.to_string(), Arc::new(create)));
+        self
+    }
+
+    /// Registers an aggregate SQL function, created on SQLite connections before any migration
+    /// or seed runs. Every connection gets its own clone of `aggregate`. See
+    /// [`Connection::create_aggregate_function`].
+    pub fn aggregate_function<A, D, T>(
+        mut self,
+        name: &str,
+        n_arg: i32,
+        flags: FunctionFlags,
+        aggregate: D,
+    ) -> Self
+    where
+        A: RefUnwindSafe + UnwindSafe,
+        D: Aggregate<A, T> + Clone + Send + Sync + 'static,
+        T: SqlFnOutput,
+    {
+        let function_name = name.to_string();
+        let create = move |connection: &Connection| {
+            connection.create_aggregate_function(&function_name, n_arg, flags, aggregate.clone())
+        };
+
+        self.sqlite_functions
+            .push((name.to_string(), Arc::new(create)));
+        self
+    }
+
+    /// Prepares the connection of a command running migrations or seeds: loads the configured
+    /// SQLite extensions and creates the registered SQL functions.
+    pub(crate) fn prepare_backend(
+        &self,
+        config: &Config,
+        backend: &mut dyn Backend,
+    ) -> AnyResult<()> {
+        config.sqlite.load_extensions(backend)?;
+
+        let Some(sqlite) = backend.downcast_mut::<SqliteBackend>() else {
+            if !self.sqlite_functions.is_empty() {
+                log::debug!("Not creating the SQL functions, only SQLite databases get them");
+            }
+            return Ok(());
+        };
+
+        for (name, create) in self.sqlite_functions.iter() {
+            create(sqlite.connection())
+                .map_err(|err| format!("Cannot create SQL function {}: {}", name, err))?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the SQL migrations of the dialect and arranges them, together with the registered
+    /// Rust migrations of the dialect, into a graph.
+    pub fn history(&self, dialect: Dialect) -> AnyResult<MigrationGraph> {
+        let rust_migrations = self
+            .rust_migrations
+            .iter()
+            .filter(|code| code.dialect() == dialect)
+            .collect::<Vec<_>>();
+
+        let mut migrations =
+            if rust_migrations.is_empty() || std::path::Path::new(&dialect.dir()).exists() {
+                read_migration_files(dialect)?
+            } else {
+                Vec::new()
+            };
+
+        for code in rust_migrations {
+            migrations.push(Migration::from_rust(code.clone())?);
+        }
+
+        Ok(MigrationGraph::new(migrations)?)
+    }
+
+    /// Parses the command line arguments and runs the command, exiting the process on error.
+    pub fn run_cli(&self) {
+        self.run(Cli::parse());
+    }
+
+    /// Runs the parsed command, exiting the process on error.
+    pub fn run(&self, cli: Cli) {
+        let config = Config::load().exit_if_error();
+        let dialect = cli.dialect.or(config.dialect).unwrap_or_default();
+        // Only resolved by the commands connecting to a database, as reading it may fail.
+        let key = || config.database_key(cli.key_file.as_deref()).exit_if_error();
+
+        match cli.command {
+            Commands::Migrate(migrate) => match migrate.command {
+                MigrateCommands::Create {
+                    name,
+                    template,
+                    values,
+                } => migration_create_command(self, &config, dialect, name, template, values)
+                    .exit_if_error(),
+                MigrateCommands::Merge { name } => {
+                    migration_merge_command(self, &config, dialect, name).exit_if_error()
+                }
+                MigrateCommands::Show { revision } => {
+                    migration_show_command(self, dialect, revision).exit_if_error()
+                }
+                MigrateCommands::Edit { revision } => {
+                    migration_edit_command(self, dialect, revision).exit_if_error()
+                }
+                MigrateCommands::Rename {
+                    revision,
+                    new_name,
+                    database_url,
+                    force,
+                } => {
+                    let key = database_url.as_ref().and_then(|_| key());
+                    migration_rename_command(
+                        self,
+                        &config,
+                        dialect,
+                        revision,
+                        new_name,
+                        database_url,
+                        key.as_deref(),
+                        force,
+                    )
+                    .exit_if_error()
+                }
+                MigrateCommands::Remove {
+                    revision,
+                    database_url,
+                    force,
+                } => {
+                    let key = if database_url.is_empty() { None } else { key() };
+                    migration_remove_command(
+                        self,
+                        dialect,
+                        revision,
+                        database_url,
+                        key.as_deref(),
+                        force,
+                    )
+                    .exit_if_error()
+                }
+                MigrateCommands::Check {
+                    database_url,
+                    format,
+                } => {
+                    let key = database_url.as_ref().and_then(|_| key());
+                    migration_check_command(self, dialect, database_url, key.as_deref(), format)
+                        .exit_if_error()
+                }
+                MigrateCommands::Test {
+                    roundtrip,
+                    temp_file,
+                    env,
+                    vars,
+                } => {
+                    let variables = Variables::new(&config, env.as_deref(), vars);
+                    migration_test_command(
+                        self,
+                        &config,
+                        dialect,
+                        env.as_deref(),
+                        &variables,
+                        roundtrip,
+                        temp_file,
+                    )
+                    .exit_if_error()
+                }
+                MigrateCommands::Up {
+                    revision,
+                    steps,
+                    database_url,
+                    database_glob,
+                    database_list,
+                    jobs,
+                    keep_going,
+                    env,
+                    vars,
+                    dry_run,
+                    format,
+                } => {
+                    let revision = revision.unwrap_or_else(|| Revision::steps(steps.unwrap()));
+                    let env = env.as_deref();
+                    let variables = Variables::new(&config, env, vars);
+
+                    match database_url {
+                        Some(database_url) => {
+                            run_with_transaction(database_url, key().as_deref(), |backend| {
+                                self.prepare_backend(&config, backend)?;
+
+                                if dry_run {
+                                    migration_migrate_up_dry_run(
+                                        self, revision, env, &variables, backend, format,
+                                    )
+                                } else {
+                                    migration_migrate_up(
+                                        self, revision, env, &variables, backend, false,
+                                    )
+                                }
+                            })
+                        }
+                        None => DatabaseSet::new(database_glob, database_list, jobs, keep_going)
+                            .and_then(|set| {
+                                migration_migrate_up_many(
+                                    self,
+                                    &config,
+                                    set,
+                                    key().as_deref(),
+                                    revision,
+                                    env,
+                                    &variables,
+                                )
+                            }),
+                    }
+                    .exit_if_error()
+                }
+                MigrateCommands::Down {
+                    revision,
+                    steps,
+                    database_url,
+                    env,
+                    vars,
+                    dry_run,
+                    format,
+                } => run_with_transaction(database_url, key().as_deref(), |backend| {
+                    self.prepare_backend(&config, backend)?;
+                    let revision = revision.unwrap_or_else(|| Revision::steps(-steps.unwrap()));
+                    let variables = Variables::new(&config, env.as_deref(), vars);
+
+                    if dry_run {
+                        migration_migrate_down_dry_run(self, revision, &variables, backend, format)
+                    } else {
+                        migration_migrate_down(self, revision, &variables, backend)
+                    }
+                })
+                .exit_if_error(),
+            },
+            Commands::History {
+                database_url,
+                format,
+            } => {
+                let key = database_url.as_ref().and_then(|_| key());
+                migration_history_command(self, dialect, database_url, key.as_deref(), format)
+                    .exit_if_error();
+            }
+            Commands::Status {
+                database_url,
+                format,
+            } => run_with_transaction(database_url, key().as_deref(), |backend| {
+                migration_status_command(self, backend, format)
+            })
+            .exit_if_error(),
+            Commands::Seed {
+                names,
+                database_url,
+                env,
+                force,
+                vars,
+            } => run_with_transaction(database_url, key().as_deref(), |backend| {
+                self.prepare_backend(&config, backend)?;
+                let variables = Variables::new(&config, env.as_deref(), vars);
+                migration_seed_command(self, backend, env, names, force, &variables)
+            })
+            .exit_if_error(),
+            Commands::Rekey {
+                database_url,
+                new_key_file,
+            } => migration_rekey_command(database_url, key().as_deref(), new_key_file)
+                .exit_if_error(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct DataMigration {
+        id: &'static str,
+        dialect: Dialect,
+    }
+
+    impl RustMigration for DataMigration {
+        fn id(&self) -> &str {
+            self.id
+        }
+
+        fn name(&self) -> &str {
+            "data"
+        }
+
+        fn dialect(&self) -> Dialect {
+            self.dialect
+        }
+
+        fn up(&self, _: &mut dyn Backend) -> AnyResult<()> {
+            Ok(())
+        }
+
+        fn down(&self, _: &mut dyn Backend) -> AnyResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn leaves_out_rust_migrations_of_other_dialects() {
+        let migrator = Migrator::new()
+            .register(DataMigration {
+                id: "20250101000000",
+                dialect: Dialect::Sqlite,
+            })
+            .register(DataMigration {
+                id: "20250102000000",
+                dialect: Dialect::Postgres,
+            });
+
+        let ids = |dialect: Dialect| {
+            let history = migrator.history(dialect).map_err(|err| err.to_string());
+            history
+                .unwrap()
+                .migrations()
+                .iter()
+                .map(|migration| (migration.stringify_id(), migration.dialect()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            ids(Dialect::Sqlite),
+            [("20250101000000".to_string(), Dialect::Sqlite)]
+        );
+        assert_eq!(
+            ids(Dialect::Postgres),
+            [("20250102000000".to_string(), Dialect::Postgres)]
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use serde::Deserialize;
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
+
+/// What happens to the non-ASCII characters of migration names.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnicodeNames {
+    /// Accented letters lose their accents (`café` becomes `cafe`), other non-ASCII characters
+    /// are treated as separators.
+    #[default]
+    Transliterate,
+
+    /// Unicode letters and digits are kept as they are.
+    Keep,
+}
+
+/// The rules turning the name given to `migrate create` into the name part of the migration
+/// filenames, set in the `names` section of the configuration file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NamesConfig {
+    /// Replaces every run of characters other than letters and digits.
+    pub separator: String,
+    pub lowercase: bool,
+    pub unicode: UnicodeNames,
+    pub max_length: usize,
+}
+
+impl Default for NamesConfig {
+    fn default() -> Self {
+        Self {
+            separator: "_".to_string(),
+            lowercase: false,
+            unicode: UnicodeNames::default(),
+            max_length: crate::MIGRATION_MAX_NAME_FOR_FILE,
+        }
+    }
+}
+
+impl NamesConfig {
+    /// Turns the name into a slug that is safe to use in a filename.
+    pub fn slugify(&self, name: &str) -> Result<String, String> {
+        if !matches!(self.separator.as_str(), "_" | "-") {
+            return Err(format!(
+                "Invalid name separator {:?}, expected \"_\" or \"-\"",
+                self.separator
+            ));
+        }
+
+        let chars: Box<dyn Iterator<Item = char>> = match self.unicode {
+            UnicodeNames::Transliterate => Box::new(name.nfkd().filter(|c| !is_combining_mark(*c))),
+            UnicodeNames::Keep => Box::new(name.nfc()),
+        };
+
+        let mut words = Vec::new();
+        let mut word = String::new();
+
+        for c in chars {
+            let allowed = match self.unicode {
+                UnicodeNames::Transliterate => c.is_ascii_alphanumeric(),
+                UnicodeNames::Keep => c.is_alphanumeric(),
+            };
+
+            if allowed {
+                if self.lowercase {
+                    word.extend(c.to_lowercase());
+                } else {
+                    word.push(c);
+                }
+            } else if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+
+        if !word.is_empty() {
+            words.push(word);
+        }
+
+        let mut slug = String::new();
+        for word in words {
+            let separator = if slug.is_empty() { "" } else { &self.separator };
+            if slug.chars().count() + separator.len() + word.chars().count() > self.max_length {
+                if slug.is_empty() {
+                    slug = word.chars().take(self.max_length).collect();
+                }
+                break;
+            }
+
+            slug.push_str(separator);
+            slug.push_str(&word);
+        }
+
+        if slug.is_empty() {
+            return Err(format!(
+                "Migration name {:?} contains no letters or digits",
+                name
+            ));
+        }
+
+        Ok(slug)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slugify(name: &str) -> Result<String, String> {
+        NamesConfig::default().slugify(name)
+    }
+
+    #[test]
+    fn collapses_repeated_separators() {
+        assert_eq!(slugify("add users table"), Ok("add_users_table".into()));
+        assert_eq!(
+            slugify("  add -- users__table!! "),
+            Ok("add_users_table".into())
+        );
+        assert_eq!(slugify("../etc/passwd"), Ok("etc_passwd".into()));
+        assert!(slugify(" -_/ ").is_err());
+        assert!(slugify("").is_err());
+    }
+
+    #[test]
+    fn applies_the_configured_separator_and_case() {
+        let config = NamesConfig {
+            separator: "-".into(),
+            lowercase: true,
+            ..NamesConfig::default()
+        };
+        assert_eq!(
+            config.slugify("Add Users_Table"),
+            Ok("add-users-table".into())
+        );
+
+        let config = NamesConfig {
+            separator: ".".into(),
+            ..NamesConfig::default()
+        };
+        assert!(config.slugify("add users").is_err());
+    }
+
+    #[test]
+    fn transliterates_or_keeps_unicode() {
+        assert_eq!(slugify("Créer café élève"), Ok("Creer_cafe_eleve".into()));
+        assert_eq!(slugify("ﬁle №1"), Ok("file_No1".into()));
+        assert_eq!(slugify("日本 users"), Ok("users".into()));
+        assert!(slugify("日本").is_err());
+
+        let config = NamesConfig {
+            unicode: UnicodeNames::Keep,
+            lowercase: true,
+            ..NamesConfig::default()
+        };
+        assert_eq!(
+            config.slugify("Créer 日本 Ünïcode"),
+            Ok("créer_日本_ünïcode".into())
+        );
+    }
+
+    #[test]
+    fn truncates_at_word_boundaries() {
+        let config = NamesConfig {
+            max_length: 12,
+            ..NamesConfig::default()
+        };
+        assert_eq!(config.slugify("add users table"), Ok("add_users".into()));
+        assert_eq!(config.slugify("add_users_ab"), Ok("add_users_ab".into()));
+        assert_eq!(
+            config.slugify("internationalization"),
+            Ok("internationa".into())
+        );
+
+        // Lengths are counted in characters, not bytes.
+        let config = NamesConfig {
+            max_length: 5,
+            unicode: UnicodeNames::Keep,
+            ..NamesConfig::default()
+        };
+        assert_eq!(config.slugify("élève été"), Ok("élève".into()));
+        assert_eq!(config.slugify("日本語のテキスト"), Ok("日本語のテ".into()));
+    }
+}
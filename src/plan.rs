@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::{AnyResult, Migration, MigrationGraph, MigrationId, Revision, Variables};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationDirection {
+    Up,
+    Down,
+}
+
+/// A single migration to apply or revert as part of a [`MigrationPlan`].
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationPlanStep {
+    pub id: MigrationId,
+    pub name: String,
+
+    /// The SQL the step executes, filled in by [`MigrationPlan::load_sql`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sql: Option<String>,
+}
+
+impl From<&Migration> for MigrationPlanStep {
+    fn from(migration: &Migration) -> Self {
+        Self {
+            id: migration.id,
+            name: migration.name().to_string(),
+            sql: None,
+        }
+    }
+}
+
+/// The migrations to run, in order, to get the database from its current migration to the
+/// target revision.
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationPlan {
+    pub direction: MigrationDirection,
+
+    /// The current migration of the database, `None` if no migration has been applied yet.
+    pub from: Option<MigrationId>,
+
+    /// The migration the database will be at once the plan is executed, `None` if every migration
+    /// gets reverted.
+    pub to: Option<MigrationId>,
+
+    pub steps: Vec<MigrationPlanStep>,
+
+    /// The migrations passed over without being executed because they are restricted to other
+    /// environments. They are recorded as skipped so that the plan can still reach its target.
+    pub skipped: Vec<MigrationPlanStep>,
+
+    /// The names of the repeatable migrations that run after the steps, because they are new or
+    /// changed since they were last applied.
+    pub repeatable: Vec<String>,
+}
+
+impl MigrationPlan {
+    pub fn up(
+        revision: &Revision,
+        graph: &MigrationGraph,
+        current: Option<&MigrationId>,
+        env: Option<&str>,
+    ) -> AnyResult<Self> {
+        let steps = Self::steps(graph, revision.revisions_to_apply(graph, current, env)?);
+        let skipped = Self::steps(graph, revision.revisions_to_skip(graph, current, env)?);
+
+        Ok(Self {
+            direction: MigrationDirection::Up,
+            from: current.copied(),
+            to: revision.resolve(graph, current)?,
+            steps,
+            skipped,
+            repeatable: Vec::new(),
+        })
+    }
+
+    /// The plan to revert the migrations back to `revision`. Migrations that were `skipped` when
+    /// applied are not reverted, only their skipped records are removed.
+    pub fn down(
+        revision: &Revision,
+        graph: &MigrationGraph,
+        current: Option<&MigrationId>,
+        skipped: &HashSet<MigrationId>,
+    ) -> AnyResult<Self> {
+        let (skipped, steps) = Self::steps(graph, revision.revisions_to_revert(graph, current)?)
+            .into_iter()
+            .partition(|step| skipped.contains(&step.id));
+
+        Ok(Self {
+            direction: MigrationDirection::Down,
+            from: current.copied(),
+            to: revision.resolve(graph, current)?,
+            steps,
+            skipped,
+            repeatable: Vec::new(),
+        })
+    }
+
+    fn steps(graph: &MigrationGraph, ids: Vec<String>) -> Vec<MigrationPlanStep> {
+        ids.iter()
+            .filter_map(|id| graph.migrations().iter().find(|m| &m.stringify_id() == id))
+            .map(MigrationPlanStep::from)
+            .collect()
+    }
+
+    /// Fills in the SQL of the steps, with its variables substituted, to show what the plan
+    /// executes.
+    pub fn load_sql(&mut self, graph: &MigrationGraph, variables: &Variables) -> AnyResult<()> {
+        for step in self.steps.iter_mut() {
+            if let Some(migration) = graph.get(&step.id) {
+                step.sql = migration.sql(self.direction, variables)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty() && self.skipped.is_empty()
+    }
+
+    /// The revision to record in the migrations table once the plan is executed.
+    pub fn target_revision(&self) -> String {
+        match &self.to {
+            Some(id) => id.to_string(),
+            None => crate::MIGRATION_BASE_REVISION.to_string(),
+        }
+    }
+}
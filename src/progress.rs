@@ -0,0 +1,190 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+    AnyResult, Backend, Migration, MigrationDirection, MigrationId, MigrationPlan, Variables,
+    split_statements,
+};
+
+/// The progress of a `migrate up` or `down` on a backend that commits implicitly on DDL (see
+/// [`Backend::transactional_ddl`]), where a failing migration cannot be rolled back.
+///
+/// The migrations are run statement by statement, each statement being committed together with a
+/// progress row holding its checksum. Once every migration of the plan is done, the progress
+/// rows are replaced by the usual versioned row. When a migration fails, running the same command
+/// again resumes after the last statement that succeeded.
+#[derive(Debug)]
+pub struct MigrationProgress {
+    direction: MigrationDirection,
+
+    /// The checksums of the statements completed so far, by migration.
+    completed: HashMap<MigrationId, Vec<Option<String>>>,
+}
+
+impl MigrationProgress {
+    fn kind(direction: MigrationDirection) -> &'static str {
+        match direction {
+            MigrationDirection::Up => crate::MIGRATION_KIND_PARTIAL_UP,
+            MigrationDirection::Down => crate::MIGRATION_KIND_PARTIAL_DOWN,
+        }
+    }
+
+    /// Reads the progress left by a failed run in the direction. Fails if a run in the opposite
+    /// direction has to be resumed first.
+    pub fn read(backend: &mut dyn Backend, direction: MigrationDirection) -> AnyResult<Self> {
+        let opposite = match direction {
+            MigrationDirection::Up => MigrationDirection::Down,
+            MigrationDirection::Down => MigrationDirection::Up,
+        };
+
+        if let Some((id, _)) = backend
+            .migration_records(Self::kind(opposite))?
+            .into_iter()
+            .last()
+        {
+            let command = match opposite {
+                MigrationDirection::Up => "migrate up",
+                MigrationDirection::Down => "migrate down",
+            };
+
+            return Err(format!(
+                "Migration {} was left partially executed by a failed '{}', run it again to \
+                 finish it first",
+                id, command
+            )
+            .into());
+        }
+
+        let mut completed = HashMap::<MigrationId, Vec<Option<String>>>::new();
+        for (id, checksum) in backend.migration_records(Self::kind(direction))? {
+            let id = MigrationId::try_from(id.as_str())
+                .map_err(|err| format!("Invalid migration in the progress records: {}", err))?;
+            completed.entry(id).or_default().push(checksum);
+        }
+
+        Ok(Self {
+            direction,
+            completed,
+        })
+    }
+
+    /// Ensures that the plan resumes the failed run, i.e. that it goes over every migration with
+    /// progress.
+    pub fn check_plan(&self, plan: &MigrationPlan) -> AnyResult<()> {
+        for id in self.completed.keys() {
+            if !plan.steps.iter().any(|step| step.id == *id) {
+                return Err(format!(
+                    "Migration {} was left partially executed by a failed run, run the same \
+                     command again to finish it",
+                    id
+                )
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the statements of the migration that were not completed yet, committing each one
+    /// together with its progress row.
+    pub fn run(
+        &self,
+        migration: &Migration,
+        backend: &mut dyn Backend,
+        variables: &Variables,
+    ) -> AnyResult<()> {
+        let done = self
+            .completed
+            .get(&migration.id)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let kind = Self::kind(self.direction);
+
+        let Some(sql) = migration.sql(self.direction, variables)? else {
+            // Rust migrations cannot be split, they run as a whole.
+            if done.is_empty() {
+                match self.direction {
+                    MigrationDirection::Up => migration.up(backend, variables)?,
+                    MigrationDirection::Down => migration.down(backend, variables)?,
+                }
+                backend.insert_migration_record(&migration.stringify_id(), kind, None)?;
+                Self::commit(backend)?;
+            }
+
+            return Ok(());
+        };
+
+        migration.load_extensions(backend)?;
+        let statements = split_statements(&sql, backend.dialect());
+        let checksums = statements
+            .iter()
+            .map(|statement| Some(crate::checksum(statement)))
+            .collect::<Vec<_>>();
+
+        if done.len() > statements.len() || checksums[..done.len()] != *done {
+            return Err(format!(
+                "Migration {} changed since it was partially executed, restore the statements \
+                 that already ran to finish it",
+                migration.id
+            )
+            .into());
+        }
+
+        if done.len() == statements.len() {
+            log::debug!("Every statement of {} already ran", migration.id);
+            return Ok(());
+        }
+
+        if !done.is_empty() {
+            println!(
+                "Resuming migration {} at statement {} of {}",
+                migration.id,
+                done.len() + 1,
+                statements.len()
+            );
+        }
+
+        for (index, statement) in statements.iter().enumerate().skip(done.len()) {
+            log::debug!("Executing statement {} of {}", index + 1, migration.id);
+            backend.execute_batch(statement).map_err(|err| {
+                format!(
+                    "{}\nStatement {} of migration {} failed, the statements before it were \
+                     committed. Fix it and run the command again to resume",
+                    err.to_string(),
+                    index + 1,
+                    migration.id
+                )
+            })?;
+
+            backend.insert_migration_record(
+                &migration.stringify_id(),
+                kind,
+                checksums[index].as_deref(),
+            )?;
+            Self::commit(backend)?;
+        }
+
+        Ok(())
+    }
+
+    /// Commits the statement run last, keeping a transaction open for the next one.
+    fn commit(backend: &mut dyn Backend) -> AnyResult<()> {
+        backend.commit()?;
+        backend.begin()
+    }
+
+    /// Removes the progress rows, once the versioned row of the completed plan is recorded.
+    pub fn clear(&self, backend: &mut dyn Backend) -> AnyResult<()> {
+        let kind = Self::kind(self.direction);
+
+        let ids = backend
+            .migration_records(kind)?
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<BTreeSet<_>>();
+        for id in ids {
+            backend.delete_migration_records(&id, kind)?;
+        }
+
+        Ok(())
+    }
+}
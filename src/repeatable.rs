@@ -0,0 +1,87 @@
+use crate::{AnyResult, Backend, Dialect, Variables};
+
+/// A migration that is executed again whenever its content changes, for objects that are
+/// redefined wholesale such as views and triggers. Repeatable migrations are the files of the
+/// `repeatable` directory and run after the versioned migrations.
+#[derive(Debug)]
+pub struct RepeatableMigration {
+    /// The filename of the migration without the `.sql` extension.
+    name: String,
+    sql: String,
+    dialect: Dialect,
+}
+
+impl RepeatableMigration {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> String {
+        format!("{}/{}.sql", self.dialect.repeatable_dir(), self.name)
+    }
+
+    /// The SQL of the migration with its variables substituted.
+    pub fn sql(&self, variables: &Variables) -> AnyResult<String> {
+        Ok(variables
+            .substitute(&self.sql)
+            .map_err(|err| format!("{} in {}", err, self.path()))?)
+    }
+
+    /// The SHA-256 checksum of the SQL of the migration, as a hex string. It is computed after
+    /// substituting the variables, so the migration also runs again when one of their values
+    /// changes.
+    pub fn checksum(&self, variables: &Variables) -> AnyResult<String> {
+        Ok(crate::checksum(&self.sql(variables)?))
+    }
+
+    /// The checksum the migration was last applied with, `None` if it was never applied.
+    pub fn applied_checksum(&self, backend: &mut dyn Backend) -> AnyResult<Option<String>> {
+        Ok(backend
+            .migration_records(crate::MIGRATION_KIND_REPEATABLE)?
+            .into_iter()
+            .rev()
+            .find(|(id, _)| *id == self.name)
+            .and_then(|(_, checksum)| checksum))
+    }
+
+    /// Executes the migration and records its checksum.
+    pub fn apply(&self, backend: &mut dyn Backend, variables: &Variables) -> AnyResult<()> {
+        log::debug!("Executing file: {}", self.path());
+        let sql = self.sql(variables)?;
+        backend.execute_batch(&sql)?;
+
+        backend.insert_migration_record(
+            &self.name,
+            crate::MIGRATION_KIND_REPEATABLE,
+            Some(&crate::checksum(&sql)),
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Reads the repeatable migrations of the dialect, ordered by their name. Returns no migrations
+/// if the directory does not exist.
+pub fn get_repeatable_migrations(dialect: Dialect) -> AnyResult<Vec<RepeatableMigration>> {
+    Ok(crate::utils::read_sql_files(&dialect.repeatable_dir())?
+        .into_iter()
+        .map(|(name, sql)| RepeatableMigration { name, sql, dialect })
+        .collect())
+}
+
+/// Returns the repeatable migrations that were never applied or changed since they were last
+/// applied, with the given variables.
+pub fn get_pending_repeatable_migrations(
+    backend: &mut dyn Backend,
+    variables: &Variables,
+) -> AnyResult<Vec<RepeatableMigration>> {
+    let mut pending = Vec::new();
+
+    for migration in get_repeatable_migrations(backend.dialect())? {
+        if migration.applied_checksum(backend)? != Some(migration.checksum(variables)?) {
+            pending.push(migration);
+        }
+    }
+
+    Ok(pending)
+}
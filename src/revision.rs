@@ -1,12 +1,20 @@
-use std::str::FromStr;
+use std::{collections::HashSet, str::FromStr};
 
-use crate::{Migration, migrations::MigrationId};
+use crate::{Migration, graph::MigrationGraph, migrations::MigrationId};
 
 // TODO: clean up the naming mess
 #[derive(Debug, Clone)]
 pub enum Revision {
     Relative(RelativeRevision),
+
+    /// A full migration id, a unique prefix of an id, or a migration name.
     Absolute(String),
+
+    /// The last migration created on or before the given date (`@YYYY-MM-DD`).
+    Date(chrono::NaiveDate),
+
+    /// The state before the first migration.
+    Base,
 }
 
 #[derive(Debug, Clone)]
@@ -16,68 +24,172 @@ pub enum RelativeRevision {
 }
 
 impl Revision {
-    fn resolve_revision_index(
+    /// Moves the target `steps` relative to the current migration: forwards if positive,
+    /// backwards if negative.
+    pub fn steps(steps: i32) -> Self {
+        Revision::Relative(RelativeRevision::Current(steps))
+    }
+
+    /// Walks `steps` parents up from the given position, following the first parent of merges.
+    /// Positions are indices into the graph, where `None` stands for the base (no migrations).
+    ///
+    /// Stepping back from a merge therefore lands on its first parent, and reverting to it also
+    /// reverts the migrations of the other merged branches.
+    fn walk_back(
+        graph: &MigrationGraph,
+        mut position: Option<usize>,
+        steps: i32,
+    ) -> Result<Option<usize>, String> {
+        for _ in 0..steps {
+            let index = position.ok_or("Offset is too large")?;
+            position = graph.parents(index).first().copied();
+        }
+
+        Ok(position)
+    }
+
+    /// Walks `steps` children down from the given position. Fails if the path branches.
+    fn walk_forward(
+        graph: &MigrationGraph,
+        mut position: Option<usize>,
+        steps: i32,
+    ) -> Result<Option<usize>, String> {
+        for _ in 0..steps {
+            let children = match position {
+                Some(index) => graph.children(index).to_vec(),
+                None => graph.roots(),
+            };
+
+            position = match children.as_slice() {
+                [] => return Err("Offset is too large".into()),
+                [child] => Some(*child),
+                _ => return Err("Offset is ambiguous, the revision has multiple children".into()),
+            };
+        }
+
+        Ok(position)
+    }
+
+    /// Resolves a migration by its full id, a unique prefix of its id, or its name.
+    fn resolve_selector(graph: &MigrationGraph, selector: &str) -> Result<usize, String> {
+        let migrations = graph.migrations();
+
+        if let Some(index) = migrations.iter().position(|m| m.stringify_id() == selector) {
+            return Ok(index);
+        }
+
+        let candidates = if selector.bytes().all(|b| b.is_ascii_digit()) {
+            migrations
+                .iter()
+                .enumerate()
+                .filter(|(_, m)| m.stringify_id().starts_with(selector))
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>()
+        } else {
+            let name = selector.replace(" ", "_");
+            migrations
+                .iter()
+                .enumerate()
+                .filter(|(_, m)| m.name() == name)
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>()
+        };
+
+        match candidates.as_slice() {
+            [] => Err(format!("Invalid revision: {}", selector)),
+            [index] => Ok(*index),
+            _ => {
+                let candidates = candidates
+                    .iter()
+                    .map(|index| {
+                        let m = &migrations[*index];
+                        format!("  {} {}", m.stringify_id(), m.name())
+                    })
+                    .collect::<Vec<_>>();
+
+                Err(format!(
+                    "Revision '{}' is ambiguous, it matches the following migrations:\n{}",
+                    selector,
+                    candidates.join("\n")
+                ))
+            }
+        }
+    }
+
+    fn resolve_position(
         &self,
-        all_migrations: &[Migration],
+        graph: &MigrationGraph,
         current: Option<usize>,
-    ) -> Result<usize, &'static str> {
+    ) -> Result<Option<usize>, String> {
         match self {
-            Revision::Absolute(revision) => all_migrations
+            Revision::Base => Ok(None),
+
+            Revision::Absolute(revision) => Self::resolve_selector(graph, revision).map(Some),
+
+            Revision::Date(date) => graph
+                .migrations()
                 .iter()
-                .position(|m| m.stringify_id() == *revision)
-                .ok_or("Invalid revision"),
+                .enumerate()
+                .filter_map(|(index, m)| m.created_at().map(|created_at| (index, created_at)))
+                .filter(|(_, created_at)| created_at.date() <= *date)
+                .max_by_key(|(_, created_at)| *created_at)
+                .map(|(index, _)| Some(index))
+                .ok_or_else(|| format!("No migration was created on or before {}", date)),
 
             Revision::Relative(revision) => match revision {
                 RelativeRevision::Head(offset) => {
                     if offset < &0 {
-                        return Err("Offset must be positive");
-                    }
-
-                    let head_revision = all_migrations.len() as i32 - 1;
-                    let target_index = head_revision - offset;
-
-                    if target_index < 0 {
-                        return Err("Offset is too large");
+                        return Err("Offset must be positive".into());
                     }
 
-                    Ok(target_index as usize)
+                    Self::walk_back(graph, graph.head()?, *offset)
                 }
                 RelativeRevision::Current(offset) => {
-                    let current_index = current.ok_or("No current migration")?;
-                    let target_index = current_index as i32 + offset;
-
-                    if target_index < 0 {
-                        return Err("Offset is too large");
+                    if *offset < 0 {
+                        Self::walk_back(graph, current, -offset)
+                    } else {
+                        Self::walk_forward(graph, current, *offset)
                     }
-
-                    if target_index >= all_migrations.len() as i32 {
-                        return Err("Offset is too large");
-                    }
-
-                    Ok(target_index as usize)
                 }
             },
         }
     }
 
-    pub fn resolve_revision_id(
+    /// Resolves the revision against the current migration of a database. Returns `None` if the
+    /// revision is the base, i.e. no migration applied at all.
+    pub fn resolve(
         &self,
-        all_migrations: &[Migration],
-    ) -> Result<String, &'static str> {
-        let target_index = self.resolve_revision_index(all_migrations, None)?;
-        Ok(all_migrations[target_index].stringify_id().clone())
+        graph: &MigrationGraph,
+        current: Option<&MigrationId>,
+    ) -> Result<Option<MigrationId>, String> {
+        let current_index = self.resolve_current_index(graph, current)?;
+        let target = self.resolve_position(graph, current_index)?;
+
+        Ok(target.map(|index| graph.migrations()[index].id))
+    }
+
+    /// Resolves the revision to a single migration, failing for `base`.
+    pub fn resolve_migration<'a>(
+        &self,
+        graph: &'a MigrationGraph,
+        current: Option<&MigrationId>,
+    ) -> Result<&'a Migration, String> {
+        let id = self
+            .resolve(graph, current)?
+            .ok_or("The revision resolves to base, which is not a migration")?;
+
+        Ok(graph.get(&id).unwrap())
     }
 
     fn resolve_current_index(
         &self,
-        all_migrations: &[Migration],
+        graph: &MigrationGraph,
         current: Option<&MigrationId>,
-    ) -> Result<Option<usize>, &'static str> {
+    ) -> Result<Option<usize>, String> {
         let current_index = match current {
             Some(current) => Some(
-                all_migrations
-                    .iter()
-                    .position(|m| m.id == *current)
+                graph
+                    .index_of(current)
                     .ok_or("Database has invalid current migration")?,
             ),
             None => None,
@@ -86,52 +198,125 @@ impl Revision {
         Ok(current_index)
     }
 
+    /// Returns the migrations to apply, in order, to get from the `current` migration to this
+    /// revision in the environment `env`. These are the ancestors of the target that are not
+    /// ancestors of `current`, leaving out the migrations restricted to other environments.
     pub fn revisions_to_apply(
         &self,
-        all_migrations: &[Migration],
+        graph: &MigrationGraph,
         current: Option<&MigrationId>,
-    ) -> Result<Vec<String>, &'static str> {
-        let current_index = self.resolve_current_index(all_migrations, current)?;
+        env: Option<&str>,
+    ) -> Result<Vec<String>, String> {
+        Ok(self
+            .pending_indices(graph, current)?
+            .into_iter()
+            .map(|index| &graph.migrations()[index])
+            .filter(|m| m.applies_to(env))
+            .map(|m| m.stringify_id())
+            .collect())
+    }
+
+    /// Returns the migrations passed over, in order, when getting from the `current` migration to
+    /// this revision in the environment `env`, because they are restricted to other environments.
+    pub fn revisions_to_skip(
+        &self,
+        graph: &MigrationGraph,
+        current: Option<&MigrationId>,
+        env: Option<&str>,
+    ) -> Result<Vec<String>, String> {
+        Ok(self
+            .pending_indices(graph, current)?
+            .into_iter()
+            .map(|index| &graph.migrations()[index])
+            .filter(|m| !m.applies_to(env))
+            .map(|m| m.stringify_id())
+            .collect())
+    }
+
+    /// The indices of the ancestors of the target that are not ancestors of `current`, in order.
+    fn pending_indices(
+        &self,
+        graph: &MigrationGraph,
+        current: Option<&MigrationId>,
+    ) -> Result<Vec<usize>, String> {
+        let current_index = self.resolve_current_index(graph, current)?;
         log::debug!("Current index: {:?}", current_index);
 
-        let target_index = self.resolve_revision_index(all_migrations, current_index)?;
+        let target_index = self.resolve_position(graph, current_index)?;
         log::debug!("Target index: {:?}", target_index);
 
-        if let Some(current_index) = current_index {
-            if target_index <= current_index {
-                return Err("Revision is already applied");
+        let Some(target_index) = target_index else {
+            if current_index.is_none() {
+                return Ok(Vec::new());
             }
+
+            return Err("Revision is already applied".into());
+        };
+
+        if current_index == Some(target_index) {
+            return Ok(Vec::new());
         }
 
-        Ok(
-            all_migrations[current_index.map(|c| c + 1).unwrap_or(0)..=target_index]
-                .iter()
-                .map(|m| m.stringify_id().clone())
-                .collect(),
-        )
+        let target_ancestors = graph.ancestors(target_index);
+        let applied = match current_index {
+            Some(current_index) => {
+                let applied = graph.ancestors(current_index);
+
+                if applied.contains(&target_index) {
+                    return Err("Revision is already applied".into());
+                }
+
+                if !target_ancestors.contains(&current_index) {
+                    return Err("Revision is not a descendant of the current migration".into());
+                }
+
+                applied
+            }
+            None => HashSet::new(),
+        };
+
+        Ok((0..graph.len())
+            .filter(|index| target_ancestors.contains(index) && !applied.contains(index))
+            .collect())
     }
 
+    /// Returns the migrations to revert, in order, to get from the `current` migration back to
+    /// this revision. These are the ancestors of `current` that are not ancestors of the target,
+    /// e.g. for `-1` from a merge, the merge and the migrations only its other parents depend on.
     pub fn revisions_to_revert(
         &self,
-        all_migrations: &[Migration],
+        graph: &MigrationGraph,
         current: Option<&MigrationId>,
-    ) -> Result<Vec<String>, &'static str> {
-        if current.is_none() {
-            return Err("No current migration");
-        }
-
+    ) -> Result<Vec<String>, String> {
         let current_index = self
-            .resolve_current_index(all_migrations, current)?
-            .unwrap();
-        let target_index = self.resolve_revision_index(all_migrations, Some(current_index))?;
+            .resolve_current_index(graph, current)?
+            .ok_or("No current migration")?;
+        let target_index = self.resolve_position(graph, Some(current_index))?;
 
-        if target_index >= current_index {
-            return Err("Revision is already reverted");
-        }
+        let applied = graph.ancestors(current_index);
 
-        Ok(all_migrations[target_index + 1..=current_index]
+        let target_ancestors = match target_index {
+            Some(target_index) => {
+                if !applied.contains(&target_index) || target_index == current_index {
+                    if graph.ancestors(target_index).contains(&current_index) {
+                        return Err("Revision is already reverted".into());
+                    }
+
+                    return Err("Revision is not an ancestor of the current migration".into());
+                }
+
+                graph.ancestors(target_index)
+            }
+            None => HashSet::new(),
+        };
+
+        Ok(graph
+            .migrations()
             .iter()
-            .map(|m| m.stringify_id().clone())
+            .enumerate()
+            .rev()
+            .filter(|(index, _)| applied.contains(index) && !target_ancestors.contains(index))
+            .map(|(_, m)| m.stringify_id())
             .collect())
     }
 }
@@ -140,6 +325,27 @@ impl TryFrom<&str> for Revision {
     type Error = &'static str;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
+        if let Some(date) = value.trim().strip_prefix('@') {
+            return chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map(Revision::Date)
+                .map_err(|_| "Invalid date, expected @YYYY-MM-DD");
+        }
+
+        let trimmed = value.trim();
+        if let Some(steps) = trimmed
+            .strip_prefix('+')
+            .or(trimmed.starts_with('-').then_some(trimmed))
+        {
+            return steps
+                .parse::<i32>()
+                .map(Revision::steps)
+                .map_err(|_| "Invalid relative revision");
+        }
+
+        if trimmed.eq_ignore_ascii_case(crate::MIGRATION_BASE_REVISION) {
+            return Ok(Revision::Base);
+        }
+
         let prepare = |s: &str| -> Result<(String, i32), &'static str> {
             let s = s.trim().to_lowercase();
             let items = s.splitn(2, ':').collect::<Vec<&str>>();
@@ -173,3 +379,237 @@ impl FromStr for Revision {
         Revision::try_from(s)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migration(id: &str, depends_on: &[&str]) -> Migration {
+        Migration::new(MigrationId::try_from(id).unwrap(), format!("m{}", id)).with_depends_on(
+            depends_on
+                .iter()
+                .map(|parent| MigrationId::try_from(*parent).unwrap())
+                .collect(),
+        )
+    }
+
+    /// 1 <- 2 and 1 <- 3 on parallel branches, merged by 4.
+    fn merged_graph() -> MigrationGraph {
+        MigrationGraph::new(vec![
+            migration("1", &[]),
+            migration("2", &[]),
+            migration("3", &["1"]),
+            migration("4", &["2", "3"]),
+        ])
+        .unwrap()
+    }
+
+    fn id(id: &str) -> MigrationId {
+        MigrationId::try_from(id).unwrap()
+    }
+
+    fn revision(s: &str) -> Revision {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn pending_migrations_across_a_merge() {
+        let graph = merged_graph();
+
+        assert_eq!(
+            revision("head").revisions_to_apply(&graph, None, None),
+            Ok(vec!["1".into(), "2".into(), "3".into(), "4".into()])
+        );
+        assert_eq!(
+            revision("head").revisions_to_apply(&graph, Some(&id("2")), None),
+            Ok(vec!["3".into(), "4".into()])
+        );
+        assert_eq!(
+            revision("head").revisions_to_apply(&graph, Some(&id("3")), None),
+            Ok(vec!["2".into(), "4".into()])
+        );
+        assert_eq!(
+            revision("4").revisions_to_apply(&graph, Some(&id("4")), None),
+            Ok(vec![])
+        );
+        assert_eq!(
+            revision("3").revisions_to_apply(&graph, Some(&id("2")), None),
+            Err("Revision is not a descendant of the current migration".into())
+        );
+        assert_eq!(
+            revision("1").revisions_to_apply(&graph, Some(&id("2")), None),
+            Err("Revision is already applied".into())
+        );
+    }
+
+    #[test]
+    fn forward_offsets_stop_at_branches() {
+        let graph = merged_graph();
+
+        assert_eq!(
+            revision("+1").revisions_to_apply(&graph, Some(&id("3")), None),
+            Ok(vec!["2".into(), "4".into()])
+        );
+        assert_eq!(
+            revision("+1").revisions_to_apply(&graph, Some(&id("1")), None),
+            Err("Offset is ambiguous, the revision has multiple children".into())
+        );
+    }
+
+    #[test]
+    fn reverting_a_merge_follows_its_first_parent() {
+        let graph = merged_graph();
+
+        // The first parent of 4 is 2, so the branch of 3 is reverted with the merge.
+        assert_eq!(
+            revision("-1").revisions_to_revert(&graph, Some(&id("4"))),
+            Ok(vec!["4".into(), "3".into()])
+        );
+        assert_eq!(
+            revision("3").revisions_to_revert(&graph, Some(&id("4"))),
+            Ok(vec!["4".into(), "2".into()])
+        );
+        assert_eq!(
+            revision("base").revisions_to_revert(&graph, Some(&id("4"))),
+            Ok(vec!["4".into(), "3".into(), "2".into(), "1".into()])
+        );
+        assert_eq!(
+            revision("3").revisions_to_revert(&graph, Some(&id("2"))),
+            Err("Revision is not an ancestor of the current migration".into())
+        );
+        assert_eq!(
+            revision("-1").revisions_to_revert(&graph, None),
+            Err("No current migration".into())
+        );
+    }
+
+    fn offset(s: &str) -> Option<i32> {
+        match s.parse::<Revision>() {
+            Ok(Revision::Relative(RelativeRevision::Current(offset))) => Some(offset),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn parses_relative_revisions() {
+        assert_eq!(offset("+2"), Some(2));
+        assert_eq!(offset("-1"), Some(-1));
+        assert_eq!(offset(" -3 "), Some(-3));
+        assert_eq!(offset("current"), Some(0));
+        assert_eq!(offset("current:-2"), Some(-2));
+        assert_eq!(offset("CURRENT:1"), Some(1));
+        assert!(matches!(
+            revision("head:1"),
+            Revision::Relative(RelativeRevision::Head(1))
+        ));
+        assert!(matches!(revision("base"), Revision::Base));
+
+        assert!("+3000000000".parse::<Revision>().is_err());
+        assert!("-3000000000".parse::<Revision>().is_err());
+        assert!("current:x".parse::<Revision>().is_err());
+    }
+
+    #[test]
+    fn resolves_relative_revisions_from_the_current_migration() {
+        let graph = merged_graph();
+
+        assert_eq!(
+            Revision::steps(1).revisions_to_apply(&graph, Some(&id("2")), None),
+            Ok(vec!["3".into(), "4".into()])
+        );
+        assert_eq!(
+            Revision::steps(1).revisions_to_apply(&graph, Some(&id("4")), None),
+            Err("Offset is too large".into())
+        );
+        assert_eq!(
+            revision("+2").revisions_to_apply(&graph, Some(&id("3")), None),
+            Err("Offset is too large".into())
+        );
+        assert_eq!(
+            revision("current:-2").revisions_to_revert(&graph, Some(&id("4"))),
+            Ok(vec!["4".into(), "3".into(), "2".into()])
+        );
+        assert_eq!(
+            Revision::steps(-3).revisions_to_revert(&graph, Some(&id("4"))),
+            Ok(vec!["4".into(), "3".into(), "2".into(), "1".into()])
+        );
+        assert_eq!(
+            Revision::steps(-4).revisions_to_revert(&graph, Some(&id("4"))),
+            Err("Offset is too large".into())
+        );
+    }
+
+    /// Timestamp ids over two months, two of the migrations sharing a name.
+    fn named_graph() -> MigrationGraph {
+        MigrationGraph::new(vec![
+            Migration::new(id("20250101090000"), "create_users"),
+            Migration::new(id("20250101120000"), "create_posts"),
+            Migration::new(id("20250215080000"), "add_email"),
+            Migration::new(id("20250301000000"), "create_posts"),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn resolves_ids_and_unique_id_prefixes() {
+        let graph = named_graph();
+        let resolve = |s: &str| revision(s).resolve(&graph, None);
+
+        assert_eq!(resolve("20250215080000"), Ok(Some(id("20250215080000"))));
+        assert_eq!(resolve("202502"), Ok(Some(id("20250215080000"))));
+        assert_eq!(resolve("2025030"), Ok(Some(id("20250301000000"))));
+        assert_eq!(resolve("2024"), Err("Invalid revision: 2024".into()));
+        assert_eq!(
+            resolve("2025010"),
+            Err(
+                "Revision '2025010' is ambiguous, it matches the following migrations:\n  \
+                 20250101090000 create_users\n  20250101120000 create_posts"
+                    .into()
+            )
+        );
+    }
+
+    #[test]
+    fn resolves_unique_names() {
+        let graph = named_graph();
+        let resolve = |s: &str| revision(s).resolve(&graph, None);
+
+        assert_eq!(resolve("add_email"), Ok(Some(id("20250215080000"))));
+        assert_eq!(resolve("add email"), Ok(Some(id("20250215080000"))));
+        assert_eq!(resolve("create_users"), Ok(Some(id("20250101090000"))));
+        assert_eq!(
+            resolve("drop_users"),
+            Err("Invalid revision: drop_users".into())
+        );
+        assert_eq!(
+            resolve("create posts"),
+            Err(
+                "Revision 'create posts' is ambiguous, it matches the following migrations:\n  \
+                 20250101120000 create_posts\n  20250301000000 create_posts"
+                    .into()
+            )
+        );
+    }
+
+    #[test]
+    fn resolves_the_last_migration_created_on_or_before_a_date() {
+        let graph = named_graph();
+        let resolve = |s: &str| revision(s).resolve(&graph, None);
+
+        assert!(matches!(revision("@2025-02-20"), Revision::Date(_)));
+        assert_eq!(resolve("@2025-01-01"), Ok(Some(id("20250101120000"))));
+        assert_eq!(resolve("@2025-02-20"), Ok(Some(id("20250215080000"))));
+        assert_eq!(resolve("@2025-03-01"), Ok(Some(id("20250301000000"))));
+        assert_eq!(
+            resolve("@2024-12-31"),
+            Err("No migration was created on or before 2024-12-31".into())
+        );
+
+        for date in ["@2025-13-01", "@20250101", "@"] {
+            assert_eq!(
+                date.parse::<Revision>().err(),
+                Some("Invalid date, expected @YYYY-MM-DD")
+            );
+        }
+    }
+}
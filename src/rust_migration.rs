@@ -0,0 +1,44 @@
+use crate::{AnyResult, Backend, Dialect};
+
+/// A migration written in Rust, for changes that cannot be expressed in SQL (e.g. re-hashing
+/// passwords). Rust migrations are registered with [`crate::Migrator::register`] and are ordered
+/// together with the SQL migrations by their id.
+///
+/// Migrations run through the [`Backend`] of the database. Those that need the underlying
+/// connection can get the concrete backend, e.g.
+/// `backend.downcast_mut::<SqliteBackend>().unwrap().connection()`, as a migration is only part
+/// of the history of its [`Self::dialect`].
+pub trait RustMigration: Send + Sync {
+    /// The id of the migration, in the same id scheme as the SQL migrations (by default a
+    /// `YYYYMMDDHHMMSS` timestamp).
+    fn id(&self) -> &str;
+
+    fn name(&self) -> &str;
+
+    /// The ids of the migrations this migration depends on. If empty, the migration depends on
+    /// the migration preceding it by id.
+    fn depends_on(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The environments the migration is restricted to. If empty, it runs in every environment.
+    fn environments(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The dialect of the databases the migration is written for. It is left out of the history
+    /// of the other dialects.
+    fn dialect(&self) -> Dialect {
+        Dialect::Sqlite
+    }
+
+    fn up(&self, backend: &mut dyn Backend) -> AnyResult<()>;
+
+    fn down(&self, backend: &mut dyn Backend) -> AnyResult<()>;
+}
+
+impl std::fmt::Debug for dyn RustMigration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RustMigration({}_{})", self.id(), self.name())
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{AnyResult, Backend, Dialect, Variables, headers::MigrationHeaders};
+
+/// A file of the `seeds` directory loading reference data or fixtures. Seeds run after the
+/// migrations and are recorded in their own table, so each one runs once per database.
+///
+/// A seed can be restricted to some environments with a `-- migrator:env dev, test` header.
+/// Seeds without the header run in every environment.
+#[derive(Debug)]
+pub struct Seed {
+    /// The filename of the seed without the `.sql` extension.
+    name: String,
+    sql: String,
+    environments: Vec<String>,
+    dialect: Dialect,
+}
+
+impl Seed {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> String {
+        format!("{}/{}.sql", self.dialect.seeds_dir(), self.name)
+    }
+
+    /// The environments the seed is restricted to, empty if it runs in every environment.
+    pub fn environments(&self) -> &[String] {
+        &self.environments
+    }
+
+    /// Whether the seed runs in the given environment. Seeds restricted to some environments do
+    /// not run when no environment is given.
+    pub fn applies_to(&self, env: Option<&str>) -> bool {
+        crate::utils::runs_in_env(&self.environments, env)
+    }
+
+    /// Whether the seed has already run against the database.
+    pub fn is_seeded(&self, backend: &mut dyn Backend) -> AnyResult<bool> {
+        Ok(backend.seeded()?.contains(&self.name))
+    }
+
+    /// Executes the seed, with its variables substituted, and records it in the seeds table.
+    pub fn run(&self, backend: &mut dyn Backend, variables: &Variables) -> AnyResult<()> {
+        let sql = variables
+            .substitute(&self.sql)
+            .map_err(|err| format!("{} in {}", err, self.path()))?;
+
+        backend.create_seeds_table()?;
+
+        log::debug!("Executing file: {}", self.path());
+        backend.execute_batch(&sql)?;
+
+        backend.record_seed(&self.name, &crate::checksum(&sql))
+    }
+}
+
+/// Reads the seeds of the dialect, ordered by their name. Returns no seeds if the directory does
+/// not exist.
+pub fn get_seeds(dialect: Dialect) -> AnyResult<Vec<Seed>> {
+    Ok(crate::utils::read_sql_files(&dialect.seeds_dir())?
+        .into_iter()
+        .map(|(name, sql)| {
+            let environments = MigrationHeaders::parse(&sql)
+                .values(crate::MIGRATION_ENV_HEADER)
+                .map(str::to_string)
+                .collect();
+
+            Seed {
+                name,
+                sql,
+                environments,
+                dialect,
+            }
+        })
+        .collect())
+}
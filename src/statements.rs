@@ -0,0 +1,321 @@
+use crate::Dialect;
+
+/// Splits SQL into its statements, at the semicolons outside of string literals, quoted
+/// identifiers, comments and `BEGIN ... END` blocks of triggers and stored routines. The
+/// statements keep their comments, those made of comments and whitespace only are dropped.
+///
+/// The dialect decides what else is not split: `#` comments and backslash escapes in strings for
+/// MySQL, `$tag$ ... $tag$` dollar-quoted bodies for PostgreSQL.
+pub fn split_statements(sql: &str, dialect: Dialect) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut has_code = false;
+    let mut depth = 0usize;
+    let mut previous_word = String::new();
+    let mut chars = sql.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '-' if chars.peek() == Some(&'-') => {
+                push_line_comment(c, &mut chars, &mut current);
+                continue;
+            }
+            '#' if dialect == Dialect::Mysql => {
+                push_line_comment(c, &mut chars, &mut current);
+                continue;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                current.push(c);
+                current.push(chars.next().unwrap());
+                let mut previous = '\0';
+                for c in chars.by_ref() {
+                    current.push(c);
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+                continue;
+            }
+            '\'' | '"' | '`' => {
+                current.push(c);
+                while let Some(next) = chars.next() {
+                    current.push(next);
+                    if next == '\\' && c != '`' && dialect == Dialect::Mysql {
+                        // MySQL escapes quotes with backslashes too.
+                        if let Some(escaped) = chars.next() {
+                            current.push(escaped);
+                        }
+                    } else if next == c {
+                        break;
+                    }
+                }
+                has_code = true;
+                previous_word.clear();
+                continue;
+            }
+            '$' if dialect == Dialect::Postgres => {
+                if let Some(tag) = dollar_quote_tag(&chars) {
+                    current.push(c);
+                    current.push_str(&tag[1..]);
+                    chars.nth(tag.chars().count() - 2);
+
+                    let mut body = String::new();
+                    while !body.ends_with(&tag) {
+                        let Some(next) = chars.next() else {
+                            break;
+                        };
+                        body.push(next);
+                    }
+                    current.push_str(&body);
+
+                    has_code = true;
+                    previous_word.clear();
+                    continue;
+                }
+                has_code = true;
+                previous_word.clear();
+            }
+            ';' if depth == 0 => {
+                if has_code {
+                    statements.push(current.trim().to_string());
+                }
+                current.clear();
+                has_code = false;
+                previous_word.clear();
+                continue;
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_' || *c == '$')
+                {
+                    word.push(c);
+                }
+                current.push_str(&word);
+                has_code = true;
+
+                let word = word.to_lowercase();
+                let following = next_word(&mut chars.clone());
+                match (word.as_str(), following.as_deref()) {
+                    // `BEGIN;`, `BEGIN WORK` and SQLite's `BEGIN IMMEDIATE` start transactions
+                    // rather than blocks.
+                    (
+                        "begin",
+                        None
+                        | Some("work" | "transaction" | "deferred" | "immediate" | "exclusive"),
+                    ) => {}
+                    ("begin", _) => depth += 1,
+                    ("case", _) if previous_word != "end" => depth += 1,
+                    // Only `BEGIN` and `CASE` are counted, not the blocks closed by `END IF` etc.
+                    ("end", Some("if" | "loop" | "while" | "repeat")) => {}
+                    ("end", _) => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                previous_word = word;
+                continue;
+            }
+            c if !c.is_whitespace() => {
+                has_code = true;
+                previous_word.clear();
+            }
+            _ => {}
+        }
+
+        current.push(c);
+    }
+
+    if has_code {
+        statements.push(current.trim().to_string());
+    }
+
+    statements
+}
+
+/// Pushes the comment starting with `first` up to the end of its line.
+fn push_line_comment(
+    first: char,
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+    current: &mut String,
+) {
+    current.push(first);
+    for c in chars.by_ref() {
+        current.push(c);
+        if c == '\n' {
+            break;
+        }
+    }
+}
+
+/// The `$tag$` opening a dollar-quoted string, the `$` already read, `None` if the dollar sign
+/// starts something else, e.g. a `$1` parameter.
+fn dollar_quote_tag(chars: &std::iter::Peekable<std::str::Chars<'_>>) -> Option<String> {
+    let mut tag = "$".to_string();
+
+    for (index, c) in chars.clone().enumerate() {
+        match c {
+            '$' => {
+                tag.push(c);
+                return Some(tag);
+            }
+            c if c.is_alphabetic() || c == '_' || (index > 0 && c.is_ascii_digit()) => tag.push(c),
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+/// The next word of the SQL, `None` if the next token is not a word.
+fn next_word(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Option<String> {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+    let mut word = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+        word.push(c);
+    }
+
+    (!word.is_empty()).then(|| word.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_at_semicolons() {
+        assert_eq!(
+            split_statements(
+                "CREATE TABLE a (id INT);\n\nINSERT INTO a VALUES (1) ;SELECT 1",
+                Dialect::Sqlite
+            ),
+            [
+                "CREATE TABLE a (id INT)",
+                "INSERT INTO a VALUES (1)",
+                "SELECT 1"
+            ]
+        );
+        assert!(split_statements(" ;; \n", Dialect::Sqlite).is_empty());
+    }
+
+    #[test]
+    fn keeps_semicolons_of_quoted_strings() {
+        assert_eq!(
+            split_statements(
+                "INSERT INTO a VALUES ('x;y', 'it''s;'); SELECT \"a;b\", `c;d` FROM a;",
+                Dialect::Sqlite
+            ),
+            [
+                "INSERT INTO a VALUES ('x;y', 'it''s;')",
+                "SELECT \"a;b\", `c;d` FROM a"
+            ]
+        );
+    }
+
+    #[test]
+    fn escapes_quotes_with_backslashes_in_mysql_only() {
+        assert_eq!(
+            split_statements("SELECT 'a\\';b'; SELECT 2;", Dialect::Mysql),
+            ["SELECT 'a\\';b'", "SELECT 2"]
+        );
+        assert_eq!(
+            split_statements("SELECT 'C:\\'; SELECT 2;", Dialect::Sqlite),
+            ["SELECT 'C:\\'", "SELECT 2"]
+        );
+        assert_eq!(
+            split_statements("SELECT 'C:\\'; SELECT 2;", Dialect::Postgres),
+            ["SELECT 'C:\\'", "SELECT 2"]
+        );
+    }
+
+    #[test]
+    fn keeps_semicolons_of_comments() {
+        let sql =
+            "-- first; comment\nSELECT 1; /* block; \n comment; */ SELECT 2;\n-- only; a comment";
+        assert_eq!(
+            split_statements(sql, Dialect::Sqlite),
+            [
+                "-- first; comment\nSELECT 1",
+                "/* block; \n comment; */ SELECT 2"
+            ]
+        );
+    }
+
+    #[test]
+    fn treats_hashes_as_comments_in_mysql_only() {
+        assert_eq!(
+            split_statements(
+                "# create; the table\nCREATE TABLE a (id INT); # trailing; comment\nSELECT 1;",
+                Dialect::Mysql
+            ),
+            [
+                "# create; the table\nCREATE TABLE a (id INT)",
+                "# trailing; comment\nSELECT 1"
+            ]
+        );
+        assert_eq!(
+            split_statements("SELECT '#'; SELECT 2;", Dialect::Mysql),
+            ["SELECT '#'", "SELECT 2"]
+        );
+        assert_eq!(
+            split_statements("SELECT 1 # 2; SELECT 3;", Dialect::Postgres),
+            ["SELECT 1 # 2", "SELECT 3"]
+        );
+    }
+
+    #[test]
+    fn keeps_dollar_quoted_bodies_in_postgres() {
+        let sql =
+            "CREATE FUNCTION f() RETURNS void AS $$ BEGIN PERFORM 1; END; $$ LANGUAGE plpgsql;
+            CREATE FUNCTION g() RETURNS text AS $body$ SELECT '$$;'; $body$ LANGUAGE sql;
+            SELECT $1, a$b FROM t;";
+        assert_eq!(
+            split_statements(sql, Dialect::Postgres),
+            [
+                "CREATE FUNCTION f() RETURNS void AS $$ BEGIN PERFORM 1; END; $$ LANGUAGE plpgsql",
+                "CREATE FUNCTION g() RETURNS text AS $body$ SELECT '$$;'; $body$ LANGUAGE sql",
+                "SELECT $1, a$b FROM t"
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_begin_end_blocks() {
+        let sql = "CREATE TRIGGER t AFTER INSERT ON a BEGIN
+                INSERT INTO b VALUES (1);
+                UPDATE c SET n = n + 1;
+            END;
+            BEGIN;
+            BEGIN IMMEDIATE;
+            SELECT 1;";
+        assert_eq!(
+            split_statements(sql, Dialect::Sqlite),
+            [
+                "CREATE TRIGGER t AFTER INSERT ON a BEGIN
+                INSERT INTO b VALUES (1);
+                UPDATE c SET n = n + 1;
+            END",
+                "BEGIN",
+                "BEGIN IMMEDIATE",
+                "SELECT 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_case_and_end_if_inside_blocks() {
+        let sql = "CREATE PROCEDURE p() BEGIN
+                IF x THEN SET y = 1; END IF;
+                WHILE z DO SET z = 0; END WHILE;
+                SET w = CASE WHEN a THEN 1 ELSE 2 END;
+                CASE v WHEN 1 THEN SET u = 1; ELSE SET u = 2; END CASE;
+            END;
+            SELECT CASE WHEN 1 THEN 'a' END; SELECT 2;";
+        let statements = split_statements(sql, Dialect::Mysql);
+
+        assert_eq!(statements.len(), 3, "{:?}", statements);
+        assert!(statements[0].starts_with("CREATE PROCEDURE"));
+        assert!(statements[0].ends_with("END CASE;\n            END"));
+        assert_eq!(statements[1], "SELECT CASE WHEN 1 THEN 'a' END");
+        assert_eq!(statements[2], "SELECT 2");
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::{
+    AnyResult, Backend, MigrationGraph, MigrationId, get_current_migration_id,
+    get_skipped_migration_ids,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationState {
+    Applied,
+
+    /// Passed over because the migration is restricted to other environments.
+    Skipped,
+    Pending,
+}
+
+impl MigrationState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MigrationState::Applied => "applied",
+            MigrationState::Skipped => "skipped",
+            MigrationState::Pending => "pending",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStatusEntry {
+    pub id: MigrationId,
+    pub name: String,
+    pub state: MigrationState,
+}
+
+/// The state of every migration in respect to the current migration of a database.
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStatus {
+    pub current: Option<MigrationId>,
+    pub heads: Vec<MigrationId>,
+
+    /// The migrations in the order they are applied.
+    pub migrations: Vec<MigrationStatusEntry>,
+}
+
+impl MigrationStatus {
+    pub fn new(
+        graph: &MigrationGraph,
+        current: Option<&MigrationId>,
+        skipped: &HashSet<MigrationId>,
+    ) -> AnyResult<Self> {
+        let applied = match current {
+            Some(current) => graph.ancestors(
+                graph
+                    .index_of(current)
+                    .ok_or("Database has invalid current migration")?,
+            ),
+            None => Default::default(),
+        };
+
+        let migrations = graph
+            .migrations()
+            .iter()
+            .enumerate()
+            .map(|(index, migration)| MigrationStatusEntry {
+                id: migration.id,
+                name: migration.name().to_string(),
+                state: if applied.contains(&index) && skipped.contains(&migration.id) {
+                    MigrationState::Skipped
+                } else if applied.contains(&index) {
+                    MigrationState::Applied
+                } else {
+                    MigrationState::Pending
+                },
+            })
+            .collect();
+
+        Ok(Self {
+            current: current.copied(),
+            heads: graph
+                .heads()
+                .into_iter()
+                .map(|head| graph.migrations()[head].id)
+                .collect(),
+            migrations,
+        })
+    }
+
+    /// The status of the migrations in the database.
+    pub fn read(graph: &MigrationGraph, backend: &mut dyn Backend) -> AnyResult<Self> {
+        let current = get_current_migration_id(backend)?;
+        let skipped = get_skipped_migration_ids(backend)?;
+
+        Self::new(graph, current.as_ref(), &skipped)
+    }
+
+    pub fn state(&self, id: &MigrationId) -> Option<MigrationState> {
+        self.migrations
+            .iter()
+            .find(|m| m.id == *id)
+            .map(|m| m.state)
+    }
+
+    pub fn count(&self, state: MigrationState) -> usize {
+        self.migrations.iter().filter(|m| m.state == state).count()
+    }
+}
@@ -0,0 +1,348 @@
+use std::collections::BTreeMap;
+
+use crate::AnyResult;
+
+/// The up and down SQL written into the files of a new migration. Templates contain `{{name}}`
+/// placeholders, filled in with the values given to `migrate create`.
+///
+/// Besides the built-in templates, a project can define its own in `migrations/templates`, as a
+/// directory holding an `up.sql` and a `down.sql` file, e.g. `migrations/templates/audit/up.sql`.
+/// Project templates take precedence over built-in templates with the same name.
+#[derive(Debug, Clone)]
+pub struct MigrationTemplate {
+    name: String,
+    up: String,
+    down: String,
+
+    /// Values used for placeholders not given to `migrate create`. Defaults can contain
+    /// placeholders themselves.
+    defaults: Vec<(String, String)>,
+}
+
+impl MigrationTemplate {
+    fn builtin(name: &str, up: &str, down: &str, defaults: &[(&str, &str)]) -> Self {
+        Self {
+            name: name.to_string(),
+            up: up.to_string(),
+            down: down.to_string(),
+            defaults: defaults
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    /// The templates shipped with the migrator.
+    pub fn builtins() -> Vec<Self> {
+        vec![
+            Self::builtin(
+                "create-table",
+                "CREATE TABLE {{table}} (\n    id INTEGER PRIMARY KEY\n);\n",
+                "DROP TABLE {{table}};\n",
+                &[],
+            ),
+            Self::builtin(
+                "add-column",
+                "ALTER TABLE {{table}} ADD COLUMN {{column}} {{type}};\n",
+                "ALTER TABLE {{table}} DROP COLUMN {{column}};\n",
+                &[("type", "TEXT")],
+            ),
+            Self::builtin(
+                "add-index",
+                "CREATE INDEX {{index}} ON {{table}} ({{column}});\n",
+                "DROP INDEX {{index}};\n",
+                &[("index", "{{table}}_{{column}}_idx")],
+            ),
+            Self::builtin(
+                "rebuild-table",
+                REBUILD_TABLE_UP_SQL,
+                REBUILD_TABLE_DOWN_SQL,
+                &[],
+            ),
+        ]
+    }
+
+    /// The project templates of the templates directory. Returns no templates if the directory
+    /// does not exist.
+    pub fn project_templates() -> AnyResult<Vec<Self>> {
+        let entries = match std::fs::read_dir(crate::MIGRATOR_TEMPLATES_DIR) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut templates = Vec::new();
+
+        for entry in entries {
+            let path = entry?.path();
+            if !path.is_dir() {
+                continue;
+            }
+
+            let read = |file: &str| {
+                std::fs::read_to_string(path.join(file))
+                    .map_err(|err| format!("Cannot read template {}: {}", path.display(), err))
+            };
+
+            templates.push(Self {
+                name: path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string(),
+                up: read("up.sql")?,
+                down: read("down.sql")?,
+                defaults: Vec::new(),
+            });
+        }
+
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(templates)
+    }
+
+    /// Finds the template with the given name, looking at the project templates first.
+    pub fn find(name: &str) -> AnyResult<Self> {
+        let mut templates = Self::project_templates()?;
+        templates.extend(Self::builtins());
+
+        if let Some(index) = templates.iter().position(|t| t.name == name) {
+            return Ok(templates.swap_remove(index));
+        }
+
+        let mut names = templates
+            .iter()
+            .map(|t| t.name.as_str())
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+
+        Err(format!(
+            "Template not found: {}, available templates: {}",
+            name,
+            names.join(", ")
+        )
+        .into())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The names of the placeholders of the template, in order of appearance.
+    pub fn placeholders(&self) -> Vec<&str> {
+        let mut placeholders = Vec::new();
+
+        for sql in [&self.up, &self.down] {
+            for placeholder in find_placeholders(sql) {
+                if !placeholders.contains(&placeholder) {
+                    placeholders.push(placeholder);
+                }
+            }
+        }
+
+        placeholders
+    }
+
+    /// Fills in the placeholders of the up and down SQL with the given values.
+    pub fn render(&self, values: &[(String, String)]) -> Result<(String, String), String> {
+        let placeholders = self.placeholders();
+
+        if let Some((key, _)) = values
+            .iter()
+            .find(|(key, _)| !placeholders.contains(&key.as_str()))
+        {
+            return Err(format!(
+                "Template {} has no placeholder {}, its placeholders are: {}",
+                self.name,
+                key,
+                placeholders.join(", ")
+            ));
+        }
+
+        let mut resolved = values.iter().cloned().collect::<BTreeMap<_, _>>();
+        for (key, default) in self.defaults.iter() {
+            if !resolved.contains_key(key) {
+                let default = fill_placeholders(default, &resolved)
+                    .map_err(|missing| self.missing_value(missing))?;
+                resolved.insert(key.clone(), default);
+            }
+        }
+
+        let up = fill_placeholders(&self.up, &resolved).map_err(|m| self.missing_value(m))?;
+        let down = fill_placeholders(&self.down, &resolved).map_err(|m| self.missing_value(m))?;
+
+        Ok((up, down))
+    }
+
+    fn missing_value(&self, placeholder: &str) -> String {
+        format!(
+            "Template {} needs a value for {}, pass it as {}=<value>",
+            self.name, placeholder, placeholder
+        )
+    }
+}
+
+/// A SQLite table rebuild, which is how SQLite changes column types and constraints. The new
+/// definition of the table is left to write: until it is, the migration fails on the `TODO`
+/// statement, naming the table to create.
+const REBUILD_TABLE_UP_SQL: &str =
+    "-- Rebuilds {{table}} with a new definition. Foreign key enforcement must be disabled while the
+-- table is dropped, as 'PRAGMA foreign_keys' has no effect inside the migration transaction.
+
+-- TODO: replace this statement with the new definition of {{table}}, as {{table}}_new, e.g.
+-- CREATE TABLE {{table}}_new (id INTEGER PRIMARY KEY, ...);
+SELECT * FROM \"TODO: create {{table}}_new with the new definition of {{table}}\";
+
+-- List the columns if the definitions have different columns.
+INSERT INTO {{table}}_new SELECT * FROM {{table}};
+DROP TABLE {{table}};
+ALTER TABLE {{table}}_new RENAME TO {{table}};
+
+-- TODO: recreate the indexes, triggers and views of {{table}}
+";
+
+/// The inverse of [`REBUILD_TABLE_UP_SQL`], rebuilding the table with its previous definition.
+const REBUILD_TABLE_DOWN_SQL: &str =
+    "-- Rebuilds {{table}} with the definition it had before the up migration, as printed by
+-- SELECT sql FROM sqlite_master WHERE name = '{{table}}' before applying it.
+
+-- TODO: replace this statement with the previous definition of {{table}}, as {{table}}_new
+SELECT * FROM \"TODO: create {{table}}_new with the previous definition of {{table}}\";
+
+-- List the columns if the definitions have different columns.
+INSERT INTO {{table}}_new SELECT * FROM {{table}};
+DROP TABLE {{table}};
+ALTER TABLE {{table}}_new RENAME TO {{table}};
+
+-- TODO: recreate the indexes, triggers and views of {{table}}
+";
+
+fn find_placeholders(sql: &str) -> Vec<&str> {
+    let mut placeholders = Vec::new();
+    let mut rest = sql;
+
+    while let Some(start) = rest.find("{{") {
+        rest = &rest[start + 2..];
+
+        if let Some(end) = rest.find("}}") {
+            placeholders.push(rest[..end].trim());
+            rest = &rest[end + 2..];
+        }
+    }
+
+    placeholders
+}
+
+/// Replaces the placeholders of the SQL with their values, returning the first placeholder
+/// without a value on failure.
+fn fill_placeholders<'a>(
+    sql: &'a str,
+    values: &BTreeMap<String, String>,
+) -> Result<String, &'a str> {
+    let mut result = String::with_capacity(sql.len());
+    let mut rest = sql;
+
+    while let Some(start) = rest.find("{{") {
+        result.push_str(&rest[..start]);
+        rest = &rest[start + 2..];
+
+        let Some(end) = rest.find("}}") else {
+            result.push_str("{{");
+            continue;
+        };
+
+        let placeholder = rest[..end].trim();
+        result.push_str(values.get(placeholder).ok_or(placeholder)?);
+        rest = &rest[end + 2..];
+    }
+
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(name: &str, values: &[(&str, &str)]) -> Result<(String, String), String> {
+        MigrationTemplate::builtins()
+            .into_iter()
+            .find(|template| template.name() == name)
+            .unwrap()
+            .render(
+                &values
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect::<Vec<_>>(),
+            )
+    }
+
+    #[test]
+    fn fills_in_placeholders_and_defaults() {
+        assert_eq!(
+            render("add-index", &[("table", "users"), ("column", "email")]),
+            Ok((
+                "CREATE INDEX users_email_idx ON users (email);\n".to_string(),
+                "DROP INDEX users_email_idx;\n".to_string()
+            ))
+        );
+        assert_eq!(
+            render("add-column", &[("table", "users")]),
+            Err("Template add-column needs a value for column, pass it as column=<value>".into())
+        );
+        assert!(render("create-table", &[("table", "t"), ("colum", "c")]).is_err());
+    }
+
+    #[test]
+    fn rebuild_table_fails_clearly_until_edited() {
+        let (up, down) = render("rebuild-table", &[("table", "users")]).unwrap();
+        let connection = rusqlite::Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch("CREATE TABLE users (id INTEGER PRIMARY KEY, age TEXT);")
+            .unwrap();
+
+        let err = connection.execute_batch(&up).unwrap_err().to_string();
+        assert!(
+            err.contains("TODO: create users_new with the new definition of users"),
+            "{}",
+            err
+        );
+        let err = connection.execute_batch(&down).unwrap_err().to_string();
+        assert!(
+            err.contains("TODO: create users_new with the previous definition of users"),
+            "{}",
+            err
+        );
+
+        let edit = |sql: &str, definition: &str| {
+            let todo = sql.lines().find(|line| line.starts_with("SELECT")).unwrap();
+            sql.replace(todo, definition)
+        };
+        connection
+            .execute_batch(&edit(
+                &up,
+                "CREATE TABLE users_new (id INTEGER PRIMARY KEY, age INTEGER NOT NULL);",
+            ))
+            .unwrap();
+        connection
+            .execute_batch(&edit(
+                &down,
+                "CREATE TABLE users_new (id INTEGER PRIMARY KEY, age TEXT);",
+            ))
+            .unwrap();
+
+        let definition: String = connection
+            .query_row(
+                "SELECT sql FROM sqlite_master WHERE name = 'users'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(
+            definition,
+            "CREATE TABLE \"users\" (id INTEGER PRIMARY KEY, age TEXT)"
+        );
+    }
+}
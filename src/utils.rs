@@ -1,54 +1,249 @@
-use rusqlite::{Connection, Transaction};
+use sha2::{Digest, Sha256};
 
-use crate::{AnyResult, cli::DatabaseUrl};
+use crate::{AnyResult, Backend, Dialect, cli::DatabaseUrl};
 
-pub fn create_migration_table(transaction: &Transaction<'_>) -> rusqlite::Result<()> {
-    transaction.execute(crate::CREATE_MIGRATIONS_TABLE_SQL, [])?;
-    transaction.execute(crate::CREATE_MIGRATIONS_TABLE_UPDATE_TRIGGER_SQL, [])?;
+/// Whether something restricted to the given environments runs in `env`. An empty list means no
+/// restriction, while restricted things never run without an environment.
+pub(crate) fn runs_in_env(environments: &[String], env: Option<&str>) -> bool {
+    environments.is_empty() || env.is_some_and(|env| environments.iter().any(|e| e == env))
+}
+
+/// The SHA-256 checksum of the content, as a hex string.
+pub fn checksum(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Reads the `.sql` files of `dir` as `(name without extension, content)` pairs, ordered by name.
+/// Returns no files if the directory does not exist.
+pub(crate) fn read_sql_files(dir: &str) -> AnyResult<Vec<(String, String)>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut files = Vec::new();
+
+    for entry in entries {
+        let entry = entry?;
+        let Some(name) = sql_file_stem(dir, &entry) else {
+            continue;
+        };
+
+        files.push((name, std::fs::read_to_string(entry.path())?));
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+/// The name of a `.sql` file of `dir` without its extension, `None` for the entries to ignore:
+/// hidden files, such as the temporary files of [`write_temp_file`], and files that are not SQL or
+/// whose name is not UTF-8, with a warning.
+pub(crate) fn sql_file_stem(dir: &str, entry: &std::fs::DirEntry) -> Option<String> {
+    let filename = entry.file_name();
+    let Some(filename) = filename.to_str() else {
+        log::warn!(
+            "Ignoring file with a non UTF-8 name in {}: {}",
+            dir,
+            filename.to_string_lossy()
+        );
+        return None;
+    };
+
+    if filename.starts_with('.') {
+        log::debug!("Ignoring hidden file in {}: {}", dir, filename);
+        return None;
+    }
+
+    let Some(stem) = filename.strip_suffix(".sql") else {
+        log::warn!("Ignoring non-SQL file in {}: {}", dir, filename);
+        return None;
+    };
+
+    Some(stem.to_string())
+}
+
+/// Writes a file that must not exist yet. The content is written to a temporary file next to it
+/// first, which is then linked to `path`, so the file either appears complete or not at all, and
+/// an existing file is never overwritten.
+pub(crate) fn write_new_file(path: &str, content: &str) -> AnyResult<()> {
+    let temp = write_temp_file(path, content)?;
+    let result = std::fs::hard_link(&temp, path);
+    let _ = std::fs::remove_file(&temp);
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+            Err(format!("Refusing to overwrite existing file: {}", path).into())
+        }
+        Err(err) => Err(format!("Cannot write {}: {}", path, err).into()),
+    }
+}
+
+/// Replaces the content of the files. Every new content is written to a temporary file next to
+/// its file first, and the temporary files are only renamed over the files once all of them are
+/// written. If a rename fails, the files already replaced get their previous content back, so a
+/// failure leaves every file as it was.
+pub(crate) fn replace_files(files: &[(String, String)]) -> AnyResult<()> {
+    let mut written = Vec::with_capacity(files.len());
+    for (path, content) in files {
+        let file = std::fs::read(path)
+            .map_err(|err| format!("Cannot read {}: {}", path, err))
+            .and_then(|previous| Ok((write_temp_file(path, content)?, previous)));
+        match file {
+            Ok(file) => written.push(file),
+            Err(err) => {
+                for (temp, _) in written {
+                    let _ = std::fs::remove_file(temp);
+                }
+                return Err(err.into());
+            }
+        }
+    }
+
+    let mut replaced = Vec::with_capacity(files.len());
+    let mut written = written.into_iter();
+    for ((path, _), (temp, previous)) in files.iter().zip(written.by_ref()) {
+        if let Err(err) = std::fs::rename(&temp, path) {
+            let _ = std::fs::remove_file(temp);
+            for (temp, _) in written {
+                let _ = std::fs::remove_file(temp);
+            }
+            for (path, previous) in replaced {
+                let _ = std::fs::write(path, previous);
+            }
+            return Err(format!("Cannot write {}: {}", path, err).into());
+        }
+        replaced.push((path, previous));
+    }
 
     Ok(())
 }
 
-pub fn table_exists<S: AsRef<str>>(conn: &Connection, table_name: S) -> rusqlite::Result<bool> {
-    let table_name = table_name.as_ref();
+/// Writes the content to a new temporary file in the directory of `path`, whose name is unique to
+/// the process and hidden from the `.sql` files of the directory.
+fn write_temp_file(path: &str, content: &str) -> Result<std::path::PathBuf, String> {
+    use std::io::Write;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
-    let mut stmt = conn.prepare(
-        format!(
-            "SELECT name FROM sqlite_master WHERE type='table' AND name='{}'",
-            table_name
-        )
-        .as_str(),
-    )?;
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
 
-    let mut rows = stmt.query([])?;
+    let target = std::path::Path::new(path);
+    let filename = target
+        .file_name()
+        .ok_or_else(|| format!("Invalid file path: {}", path))?
+        .to_string_lossy();
 
-    Ok(rows.next().ok().flatten().is_some())
+    loop {
+        let temp = target.with_file_name(format!(
+            ".{}.{}.{}.tmp",
+            filename,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let mut file = match std::fs::File::create_new(&temp) {
+            Ok(file) => file,
+            // Left behind by a process that had the same id.
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(format!("Cannot write {}: {}", path, err)),
+        };
+
+        if let Err(err) = file
+            .write_all(content.as_bytes())
+            .and_then(|_| file.sync_all())
+        {
+            drop(file);
+            let _ = std::fs::remove_file(&temp);
+            return Err(format!("Cannot write {}: {}", path, err));
+        }
+
+        return Ok(temp);
+    }
 }
 
-pub fn create_migrations_dir() -> std::io::Result<()> {
-    std::fs::create_dir_all(crate::MIGRATOR_UP_DIR)?;
-    std::fs::create_dir_all(crate::MIGRATOR_DOWN_DIR)?;
+pub fn create_migrations_dir(dialect: Dialect) -> std::io::Result<()> {
+    std::fs::create_dir_all(dialect.up_dir())?;
+    std::fs::create_dir_all(dialect.down_dir())?;
     log::debug!("Created migrations directory");
     Ok(())
 }
 
-pub fn run_with_transaction<T, F>(db_url: DatabaseUrl, callback: F) -> AnyResult<T>
+/// Connects to the database, unlocked with the key if it is encrypted, and runs the callback in a
+/// transaction, committed if the callback succeeds and rolled back otherwise.
+pub fn run_with_transaction<T, F>(
+    db_url: DatabaseUrl,
+    key: Option<&str>,
+    callback: F,
+) -> AnyResult<T>
 where
-    F: FnOnce(&Transaction<'_>) -> AnyResult<T>,
+    F: FnOnce(&mut dyn Backend) -> AnyResult<T>,
 {
-    let mut conn = db_url.open_connection()?;
-    let transaction = conn.transaction()?;
-    let result = callback(&transaction);
+    let mut backend = db_url.connect(key)?;
+    backend.begin()?;
+    let result = backend.lock().and_then(|_| callback(backend.as_mut()));
 
     match result {
         Ok(value) => {
-            transaction.commit()?;
+            backend.commit()?;
             Ok(value)
         }
         Err(err) => {
             log::debug!("Rolling back transaction due to error: {}", err.to_string());
-            transaction.rollback()?;
+            backend.rollback()?;
             Err(err)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_new_files_without_overwriting() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new.sql").to_string_lossy().to_string();
+
+        assert!(write_new_file(&path, "SELECT 1;").is_ok());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "SELECT 1;");
+
+        let err = write_new_file(&path, "SELECT 2;").unwrap_err().to_string();
+        assert_eq!(
+            err,
+            format!("Refusing to overwrite existing file: {}", path)
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "SELECT 1;");
+
+        let missing = dir.path().join("missing/new.sql");
+        assert!(write_new_file(&missing.to_string_lossy(), "").is_err());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn replaces_every_file_or_none() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        std::fs::write(path("a.sql"), "a").unwrap();
+        std::fs::write(path("b.sql"), "b").unwrap();
+
+        let files = vec![(path("a.sql"), "a2".into()), (path("b.sql"), "b2".into())];
+        assert!(replace_files(&files).is_ok());
+        assert_eq!(std::fs::read_to_string(path("a.sql")).unwrap(), "a2");
+        assert_eq!(std::fs::read_to_string(path("b.sql")).unwrap(), "b2");
+
+        // The second file cannot be written, so the first one is not replaced either.
+        let files = vec![
+            (path("a.sql"), "a3".into()),
+            (path("missing/b.sql"), "b3".into()),
+        ];
+        assert!(replace_files(&files).is_err());
+        assert_eq!(std::fs::read_to_string(path("a.sql")).unwrap(), "a2");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+
+use crate::Config;
+
+/// The values substituted for the `${NAME}` placeholders of the SQL migrations. A placeholder is
+/// escaped by doubling its dollar sign: `$${NAME}` is kept as `${NAME}`.
+///
+/// Values given on the command line take precedence over environment variables, which take
+/// precedence over the configuration file.
+#[derive(Debug, Default, Clone)]
+pub struct Variables {
+    explicit: BTreeMap<String, String>,
+    config: BTreeMap<String, String>,
+}
+
+impl Variables {
+    pub fn new(config: &Config, env: Option<&str>, explicit: Vec<(String, String)>) -> Self {
+        Self {
+            explicit: explicit.into_iter().collect(),
+            config: config.vars(env),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.explicit
+            .get(name)
+            .cloned()
+            .or_else(|| std::env::var(name).ok())
+            .or_else(|| self.config.get(name).cloned())
+    }
+
+    /// Replaces the placeholders of the SQL with the values of the variables, failing on undefined
+    /// variables and malformed placeholders.
+    pub fn substitute(&self, sql: &str) -> Result<String, String> {
+        let mut result = String::with_capacity(sql.len());
+        let mut rest = sql;
+
+        while let Some(start) = rest.find('$') {
+            result.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            if let Some(escaped) = rest.strip_prefix("$${") {
+                result.push_str("${");
+                rest = escaped;
+                continue;
+            }
+
+            let Some(placeholder) = rest.strip_prefix("${") else {
+                result.push('$');
+                rest = &rest[1..];
+                continue;
+            };
+
+            let name = placeholder
+                .find('}')
+                .map(|end| &placeholder[..end])
+                .filter(|name| is_variable_name(name))
+                .ok_or_else(|| {
+                    let line = placeholder.lines().next().unwrap_or_default();
+                    format!("Invalid variable placeholder: ${{{}", line)
+                })?;
+
+            let value = self
+                .get(name)
+                .ok_or_else(|| format!("Undefined variable: {}", name))?;
+
+            result.push_str(&value);
+            rest = &placeholder[name.len() + 1..];
+        }
+
+        result.push_str(rest);
+
+        Ok(result)
+    }
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parses a `KEY=VALUE` command line argument.
+pub fn parse_variable(arg: &str) -> Result<(String, String), String> {
+    let (name, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("Expected KEY=VALUE, got: {}", arg))?;
+
+    if !is_variable_name(name) {
+        return Err(format!("Invalid variable name: {}", name));
+    }
+
+    Ok((name.to_string(), value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables(values: &[(&str, &str)]) -> Variables {
+        Variables::new(
+            &Config::default(),
+            None,
+            values
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn substitutes_placeholders() {
+        let variables = variables(&[("MIGRATOR_TEST_TABLE", "users"), ("days", "30")]);
+
+        assert_eq!(
+            variables.substitute("DELETE FROM ${MIGRATOR_TEST_TABLE} WHERE age > ${days};"),
+            Ok("DELETE FROM users WHERE age > 30;".to_string())
+        );
+        assert_eq!(
+            variables.substitute("${days}${days}"),
+            Ok("3030".to_string())
+        );
+    }
+
+    #[test]
+    fn keeps_escaped_placeholders_and_other_dollar_signs() {
+        let variables = variables(&[("days", "30")]);
+
+        assert_eq!(
+            variables.substitute("SELECT '$${days}', '${days}'"),
+            Ok("SELECT '${days}', '30'".to_string())
+        );
+        assert_eq!(
+            variables.substitute("SELECT $1, $$body$$, $ {days}, '$'"),
+            Ok("SELECT $1, $$body$$, $ {days}, '$'".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_undefined_variables() {
+        assert_eq!(
+            variables(&[]).substitute("SELECT ${MIGRATOR_TEST_UNDEFINED}"),
+            Err("Undefined variable: MIGRATOR_TEST_UNDEFINED".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_placeholders() {
+        let variables = variables(&[("days", "30")]);
+
+        assert_eq!(
+            variables.substitute("SELECT ${days\nFROM t"),
+            Err("Invalid variable placeholder: ${days".to_string())
+        );
+        assert_eq!(
+            variables.substitute("SELECT ${}"),
+            Err("Invalid variable placeholder: ${}".to_string())
+        );
+        assert_eq!(
+            variables.substitute("SELECT ${1st}"),
+            Err("Invalid variable placeholder: ${1st}".to_string())
+        );
+        assert_eq!(
+            variables.substitute("SELECT ${a-b}"),
+            Err("Invalid variable placeholder: ${a-b}".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_variable_arguments() {
+        assert_eq!(
+            parse_variable("days=30=x"),
+            Ok(("days".to_string(), "30=x".to_string()))
+        );
+        assert!(parse_variable("days").is_err());
+        assert!(parse_variable("1st=30").is_err());
+    }
+}
@@ -0,0 +1,233 @@
+//! Integration tests of the MySQL backend. They run the migrator binary against the MySQL or
+//! MariaDB server of `MIGRATOR_TEST_MYSQL_URL` and are skipped when it is not set, e.g.
+//!
+//! ```bash
+//! mariadb-install-db --datadir=/tmp/mariadb --auth-root-authentication-method=normal
+//! mariadbd --datadir=/tmp/mariadb --port=33069 --socket=/tmp/mariadb.sock &
+//! MIGRATOR_TEST_MYSQL_URL=mysql://root@127.0.0.1:33069 cargo test --test mysql
+//! ```
+//!
+//! Every test works in a database of its own, dropped at the end of the test.
+
+use std::{
+    process::{Command, Output},
+    thread,
+    time::Duration,
+};
+
+use mysql::{Conn, Opts, prelude::Queryable};
+
+struct TestProject {
+    dir: tempfile::TempDir,
+    server_url: String,
+    database: String,
+}
+
+impl TestProject {
+    /// `None` if no server is configured for the tests.
+    fn new(name: &str) -> Option<Self> {
+        let Ok(server_url) = std::env::var("MIGRATOR_TEST_MYSQL_URL") else {
+            eprintln!("MIGRATOR_TEST_MYSQL_URL is not set, skipping");
+            return None;
+        };
+
+        let project = Self {
+            dir: tempfile::tempdir().unwrap(),
+            server_url: server_url.trim_end_matches('/').to_string(),
+            database: format!("migrator_test_{}_{}", name, std::process::id()),
+        };
+        project
+            .server()
+            .query_drop(format!(
+                "DROP DATABASE IF EXISTS {0}; CREATE DATABASE {0}",
+                project.database
+            ))
+            .unwrap();
+
+        Some(project)
+    }
+
+    fn database_url(&self) -> String {
+        format!("{}/{}", self.server_url, self.database)
+    }
+
+    fn server(&self) -> Conn {
+        Conn::new(Opts::from_url(&self.server_url).unwrap()).unwrap()
+    }
+
+    fn client(&self) -> Conn {
+        Conn::new(Opts::from_url(&self.database_url()).unwrap()).unwrap()
+    }
+
+    fn migration(&self, stem: &str, up: &str, down: &str) {
+        for (direction, sql) in [("up", up), ("down", down)] {
+            let dir = self.dir.path().join("migrations/mysql").join(direction);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join(format!("{}.sql", stem)), sql).unwrap();
+        }
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let database_url = self.database_url();
+        let mut command = Command::new(env!("CARGO_BIN_EXE_migrator"));
+        command
+            .current_dir(self.dir.path())
+            .args(args)
+            .args(["--database-url", &database_url]);
+        command
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+
+    fn table_exists(&self, table: &str) -> bool {
+        let count: Option<u64> = self
+            .client()
+            .exec_first(
+                "SELECT COUNT(*) FROM information_schema.tables
+                WHERE table_schema = DATABASE() AND table_name = ?",
+                (table,),
+            )
+            .unwrap();
+
+        count == Some(1)
+    }
+
+    fn records(&self, kind: &str) -> Vec<String> {
+        self.client()
+            .exec(
+                "SELECT id FROM __migrations__ WHERE kind = ? ORDER BY seq",
+                (kind,),
+            )
+            .unwrap()
+    }
+}
+
+impl Drop for TestProject {
+    fn drop(&mut self) {
+        // Errors are ignored, as panicking again while a failed test unwinds would abort.
+        if let Ok(opts) = Opts::from_url(&self.server_url)
+            && let Ok(mut server) = Conn::new(opts)
+        {
+            let _ = server.query_drop(format!("DROP DATABASE IF EXISTS {}", self.database));
+        }
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn applies_and_reverts_migrations() {
+    let Some(project) = TestProject::new("roundtrip") else {
+        return;
+    };
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE users (id INT AUTO_INCREMENT PRIMARY KEY);",
+        "DROP TABLE users;",
+    );
+    project.migration(
+        "20250102000000_posts",
+        "CREATE TABLE posts (id INT AUTO_INCREMENT PRIMARY KEY);
+        INSERT INTO posts VALUES (1);",
+        "DROP TABLE posts;",
+    );
+
+    let output = project.run(&["migrate", "up", "head"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(project.table_exists("users"));
+    assert!(project.table_exists("posts"));
+    assert_eq!(project.records("versioned"), ["20250102000000"]);
+    assert!(project.records("partial-up").is_empty());
+
+    let output = project.run(&["status"]);
+    assert!(stdout(&output).contains("Applied: 2, skipped: 0, pending: 0"));
+
+    let output = project.run(&["migrate", "down", "base"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!project.table_exists("users"));
+    assert!(!project.table_exists("posts"));
+    assert_eq!(project.records("versioned"), ["20250102000000", "base"]);
+    assert!(project.records("partial-down").is_empty());
+}
+
+#[test]
+fn resumes_a_partially_executed_migration() {
+    let Some(project) = TestProject::new("resume") else {
+        return;
+    };
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE users (id INT PRIMARY KEY);
+        INSERT INTO users VALUES (1);
+        INSERT INTO missing VALUES (1);
+        CREATE TABLE posts (id INT PRIMARY KEY);",
+        "DROP TABLE posts; DROP TABLE users;",
+    );
+
+    let output = project.run(&["migrate", "up", "head"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Statement 3 of migration"));
+
+    // The DDL committed implicitly, and the statements that succeeded are recorded.
+    assert!(project.table_exists("users"));
+    assert!(!project.table_exists("posts"));
+    assert_eq!(
+        project.records("partial-up"),
+        ["20250101000000", "20250101000000"]
+    );
+    assert!(project.records("versioned").is_empty());
+
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE users (id INT PRIMARY KEY);
+        INSERT INTO users VALUES (1);
+        INSERT INTO users VALUES (2);
+        CREATE TABLE posts (id INT PRIMARY KEY);",
+        "DROP TABLE posts; DROP TABLE users;",
+    );
+
+    let output = project.run(&["migrate", "up", "head"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("Resuming migration 20250101000000 at statement 3 of 4"));
+    assert!(project.table_exists("posts"));
+    assert!(project.records("partial-up").is_empty());
+    assert_eq!(project.records("versioned"), ["20250101000000"]);
+
+    let users: Vec<i32> = project
+        .client()
+        .query("SELECT id FROM users ORDER BY id")
+        .unwrap();
+    assert_eq!(users, [1, 2]);
+}
+
+#[test]
+fn waits_for_the_named_lock() {
+    let Some(project) = TestProject::new("lock") else {
+        return;
+    };
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE users (id INT PRIMARY KEY);",
+        "DROP TABLE users;",
+    );
+
+    let mut holder = project.client();
+    holder
+        .query_drop("SELECT GET_LOCK(CONCAT('__migrations__.', DATABASE()), 0)")
+        .unwrap();
+
+    let mut child = project.command(&["migrate", "up", "head"]).spawn().unwrap();
+    thread::sleep(Duration::from_millis(500));
+    assert!(child.try_wait().unwrap().is_none());
+    assert!(!project.table_exists("users"));
+
+    holder
+        .query_drop("SELECT RELEASE_LOCK(CONCAT('__migrations__.', DATABASE()))")
+        .unwrap();
+    assert!(child.wait().unwrap().success());
+    assert!(project.table_exists("users"));
+}
@@ -0,0 +1,238 @@
+//! Integration tests of the PostgreSQL backend. They run the migrator binary against the server
+//! of `MIGRATOR_TEST_POSTGRES_URL` and are skipped when it is not set, e.g.
+//!
+//! ```bash
+//! initdb -D /tmp/pg -A trust -U postgres
+//! pg_ctl -D /tmp/pg -o "-p 54329 -k /tmp" start
+//! MIGRATOR_TEST_POSTGRES_URL=postgres://postgres@localhost:54329/postgres cargo test --test postgres
+//! ```
+//!
+//! Every test works in a schema of its own, dropped at the end of the test.
+
+use std::{
+    process::{Command, Output},
+    thread,
+    time::Duration,
+};
+
+use postgres::{Client, NoTls};
+
+struct TestProject {
+    dir: tempfile::TempDir,
+    server_url: String,
+    schema: String,
+}
+
+impl TestProject {
+    /// `None` if no server is configured for the tests.
+    fn new(name: &str) -> Option<Self> {
+        let Ok(server_url) = std::env::var("MIGRATOR_TEST_POSTGRES_URL") else {
+            eprintln!("MIGRATOR_TEST_POSTGRES_URL is not set, skipping");
+            return None;
+        };
+
+        let project = Self {
+            dir: tempfile::tempdir().unwrap(),
+            server_url,
+            schema: format!("migrator_test_{}_{}", name, std::process::id()),
+        };
+        project.drop_schema();
+
+        Some(project)
+    }
+
+    fn database_url(&self) -> String {
+        let separator = if self.server_url.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        format!("{}{}schema={}", self.server_url, separator, self.schema)
+    }
+
+    fn client(&self) -> Client {
+        Client::connect(&self.server_url, NoTls).unwrap()
+    }
+
+    /// Writes a migration, replacing `{schema}` in the SQL with the schema of the test.
+    fn migration(&self, stem: &str, up: &str, down: &str) {
+        for (direction, sql) in [("up", up), ("down", down)] {
+            let dir = self.dir.path().join("migrations/postgres").join(direction);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(
+                dir.join(format!("{}.sql", stem)),
+                sql.replace("{schema}", &self.schema),
+            )
+            .unwrap();
+        }
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let database_url = self.database_url();
+        let mut command = Command::new(env!("CARGO_BIN_EXE_migrator"));
+        command
+            .current_dir(self.dir.path())
+            .args(args)
+            .args(["--database-url", &database_url]);
+        command
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+
+    fn table_exists(&self, table: &str) -> bool {
+        self.client()
+            .query_one(
+                "SELECT to_regclass($1) IS NOT NULL",
+                &[&format!("{}.{}", self.schema, table)],
+            )
+            .unwrap()
+            .get(0)
+    }
+
+    fn versioned_ids(&self) -> Vec<String> {
+        self.client()
+            .query(
+                &format!(
+                    "SELECT id FROM {}.__migrations__ WHERE kind = 'versioned' ORDER BY seq",
+                    self.schema
+                ),
+                &[],
+            )
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect()
+    }
+
+    fn drop_schema(&self) {
+        self.client()
+            .batch_execute(&format!("DROP SCHEMA IF EXISTS {} CASCADE", self.schema))
+            .unwrap();
+    }
+}
+
+impl Drop for TestProject {
+    fn drop(&mut self) {
+        // Errors are ignored, as panicking again while a failed test unwinds would abort.
+        if let Ok(mut client) = Client::connect(&self.server_url, NoTls) {
+            let _ = client.batch_execute(&format!("DROP SCHEMA IF EXISTS {} CASCADE", self.schema));
+        }
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn applies_and_reverts_migrations_in_the_schema() {
+    let Some(project) = TestProject::new("roundtrip") else {
+        return;
+    };
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE {schema}.users (id SERIAL PRIMARY KEY);",
+        "DROP TABLE {schema}.users;",
+    );
+    project.migration(
+        "20250102000000_posts",
+        "CREATE TABLE {schema}.posts (id SERIAL PRIMARY KEY);",
+        "DROP TABLE {schema}.posts;",
+    );
+
+    let output = project.run(&["migrate", "up", "head"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(project.table_exists("__migrations__"));
+    assert!(project.table_exists("users"));
+    assert!(project.table_exists("posts"));
+    assert_eq!(project.versioned_ids(), ["20250102000000"]);
+
+    let output = project.run(&["status"]);
+    assert!(stdout(&output).contains("Applied: 2, skipped: 0, pending: 0"));
+
+    let output = project.run(&["migrate", "down", "base"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!project.table_exists("users"));
+    assert!(!project.table_exists("posts"));
+    assert_eq!(project.versioned_ids(), ["20250102000000", "base"]);
+}
+
+#[test]
+fn rolls_back_every_migration_when_one_fails() {
+    let Some(project) = TestProject::new("rollback") else {
+        return;
+    };
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE {schema}.users (id SERIAL PRIMARY KEY);",
+        "DROP TABLE {schema}.users;",
+    );
+    project.migration(
+        "20250102000000_broken",
+        "CREATE TABLE {schema}.posts (id SERIAL PRIMARY KEY); SELECT 1 / 0;",
+        "DROP TABLE {schema}.posts;",
+    );
+
+    let output = project.run(&["migrate", "up", "head"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("division by zero"));
+
+    assert!(!project.table_exists("users"));
+    assert!(!project.table_exists("posts"));
+    assert!(!project.table_exists("__migrations__"));
+}
+
+#[test]
+fn waits_for_the_advisory_lock() {
+    let Some(project) = TestProject::new("lock") else {
+        return;
+    };
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE {schema}.users (id SERIAL PRIMARY KEY);",
+        "DROP TABLE {schema}.users;",
+    );
+
+    let mut holder = project.client();
+    let mut transaction = holder.transaction().unwrap();
+    transaction
+        .execute(
+            "SELECT pg_advisory_xact_lock(hashtext('__migrations__'), hashtext($1))",
+            &[&project.schema],
+        )
+        .unwrap();
+
+    let mut child = project.command(&["migrate", "up", "head"]).spawn().unwrap();
+    thread::sleep(Duration::from_millis(500));
+    assert!(child.try_wait().unwrap().is_none());
+    assert!(!project.table_exists("users"));
+
+    transaction.commit().unwrap();
+    assert!(child.wait().unwrap().success());
+    assert!(project.table_exists("users"));
+}
+
+#[test]
+fn removes_migrations_of_the_database_dialect() {
+    let Some(project) = TestProject::new("remove") else {
+        return;
+    };
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE {schema}.users (id SERIAL PRIMARY KEY);",
+        "DROP TABLE {schema}.users;",
+    );
+
+    // The configured dialect is SQLite, which has no migrations in the project.
+    let output = project.run(&["migrate", "remove", "head"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(
+        !project
+            .dir
+            .path()
+            .join("migrations/postgres/up/20250101000000_users.sql")
+            .exists()
+    );
+}
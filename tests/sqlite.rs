@@ -0,0 +1,1070 @@
+//! Integration tests of the SQLite backend. They run the migrator binary in a temporary project
+//! against a database file of the project.
+
+use std::process::Output;
+
+use rusqlite::Connection;
+
+struct TestProject {
+    dir: tempfile::TempDir,
+}
+
+impl TestProject {
+    fn new() -> Self {
+        Self {
+            dir: tempfile::tempdir().unwrap(),
+        }
+    }
+
+    fn database_path(&self) -> String {
+        self.dir.path().join("app.db").to_string_lossy().to_string()
+    }
+
+    fn connection(&self) -> Connection {
+        Connection::open(self.database_path()).unwrap()
+    }
+
+    fn write(&self, path: &str, content: &str) {
+        let path = self.dir.path().join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn migration(&self, stem: &str, up: &str, down: &str) {
+        self.write(&format!("migrations/sqlite/up/{}.sql", stem), up);
+        self.write(&format!("migrations/sqlite/down/{}.sql", stem), down);
+    }
+
+    /// Runs the migrator in the project, against its database.
+    fn run(&self, args: &[&str]) -> Output {
+        self.run_without_database(&[args, &["--database-url", &self.database_path()]].concat())
+    }
+
+    fn run_without_database(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+
+    /// The migrator command run in the project, without the environment variables it reads.
+    fn command(&self, args: &[&str]) -> std::process::Command {
+        let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_migrator"));
+        command
+            .current_dir(self.dir.path())
+            .args(args)
+            .env_remove("MIGRATOR_ENV")
+            .env_remove("MIGRATOR_DB_KEY")
+            .env_remove("MIGRATOR_DB_NEW_KEY");
+        command
+    }
+
+    fn table_exists(&self, table: &str) -> bool {
+        self.connection()
+            .query_row(
+                "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                [table],
+                |row| row.get::<_, i64>(0),
+            )
+            .unwrap()
+            == 1
+    }
+
+    fn records(&self, kind: &str) -> Vec<String> {
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare("SELECT id FROM __migrations__ WHERE kind = ?1 ORDER BY seq")
+            .unwrap();
+        statement
+            .query_map([kind], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn skips_and_unskips_migrations_of_other_environments() {
+    let project = TestProject::new();
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE users (id INTEGER PRIMARY KEY);",
+        "DROP TABLE users;",
+    );
+    project.migration(
+        "20250102000000_dev_users",
+        "-- migrator:env dev\nINSERT INTO users (id) VALUES (1);",
+        "DELETE FROM users WHERE id = 1;",
+    );
+
+    let output = project.run(&["migrate", "up", "head", "--env", "prod"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("Applying migration: 20250101000000"));
+    assert!(stdout(&output).contains("Skipping migration: 20250102000000"));
+    assert_eq!(project.records("skipped"), ["20250102000000"]);
+    assert_eq!(project.records("versioned"), ["20250102000000"]);
+
+    let output = project.run(&["migrate", "down", "-1"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout(&output), "Unskipping migration: 20250102000000\n");
+    assert!(project.records("skipped").is_empty());
+    assert_eq!(
+        project.records("versioned"),
+        ["20250102000000", "20250101000000"]
+    );
+    assert!(project.table_exists("users"));
+
+    let output = project.run(&["migrate", "up", "head", "--env", "dev"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("Applying migration: 20250102000000"));
+    let users = project
+        .connection()
+        .query_row("SELECT count(*) FROM users", [], |row| row.get::<_, i64>(0))
+        .unwrap();
+    assert_eq!(users, 1);
+}
+
+#[test]
+fn substitutes_variables_in_repeatable_migrations_and_seeds() {
+    let project = TestProject::new();
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT);",
+        "DROP TABLE users;",
+    );
+    project.write(
+        "migrations/sqlite/repeatable/admins.sql",
+        "DROP VIEW IF EXISTS admins;
+        CREATE VIEW admins AS SELECT * FROM users WHERE email = '${admin_email}';",
+    );
+    project.write(
+        "migrations/sqlite/seeds/admin.sql",
+        "INSERT INTO users (email) VALUES ('${admin_email}');",
+    );
+    let admins = || {
+        project
+            .connection()
+            .query_row("SELECT count(*) FROM admins", [], |row| {
+                row.get::<_, i64>(0)
+            })
+            .unwrap()
+    };
+
+    let output = project.run(&["migrate", "up", "head"]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("Undefined variable: admin_email in migrations/sqlite/repeatable/admins.sql")
+    );
+
+    let output = project.run(&[
+        "migrate",
+        "up",
+        "head",
+        "--var",
+        "admin_email=a@example.com",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = project.run(&["seed", "--var", "admin_email=a@example.com"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(admins(), 1);
+
+    // The same file with another value is a new checksum, so the view is created again.
+    let output = project.run(&[
+        "migrate",
+        "up",
+        "head",
+        "--var",
+        "admin_email=b@example.com",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("Applying repeatable migration: admins"));
+    assert_eq!(admins(), 0);
+
+    let output = project.run(&[
+        "migrate",
+        "up",
+        "head",
+        "--var",
+        "admin_email=b@example.com",
+    ]);
+    assert_eq!(stdout(&output), "Already up to date\n");
+}
+
+#[test]
+fn removing_a_migration_rewrites_the_headers_of_its_children() {
+    let project = TestProject::new();
+    project.migration("20250101000000_users", "SELECT 1;", "SELECT 1;");
+    project.migration("20250102000000_posts", "SELECT 2;", "SELECT 2;");
+    project.migration(
+        "20250103000000_comments",
+        "-- migrator:depends-on 20250102000000\nSELECT 3;",
+        "SELECT 3;",
+    );
+    project.migration(
+        "20250104000000_likes",
+        "-- migrator:depends-on 20250102000000, 20250103000000\nSELECT 4;",
+        "SELECT 4;",
+    );
+
+    let output = project.run_without_database(&["migrate", "remove", "20250102000000"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("use --force"));
+
+    let output = project.run_without_database(&["migrate", "remove", "20250102000000", "--force"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let up = |stem: &str| {
+        std::fs::read_to_string(
+            project
+                .dir
+                .path()
+                .join(format!("migrations/sqlite/up/{}.sql", stem)),
+        )
+        .unwrap()
+    };
+    assert_eq!(
+        up("20250103000000_comments"),
+        "-- migrator:depends-on 20250101000000\nSELECT 3;"
+    );
+    assert_eq!(
+        up("20250104000000_likes"),
+        "-- migrator:depends-on 20250103000000, 20250101000000\nSELECT 4;"
+    );
+    assert!(
+        !project
+            .dir
+            .path()
+            .join("migrations/sqlite/down/20250102000000_posts.sql")
+            .exists()
+    );
+    let leftovers = std::fs::read_dir(project.dir.path().join("migrations/sqlite/up"))
+        .unwrap()
+        .count();
+    assert_eq!(leftovers, 3);
+}
+
+#[test]
+fn tests_migrations_against_a_scratch_database() {
+    let project = TestProject::new();
+
+    // Without migrations, the roundtrip has nothing to revert.
+    for dir in ["up", "down"] {
+        std::fs::create_dir_all(project.dir.path().join("migrations/sqlite").join(dir)).unwrap();
+    }
+    let output = project.run_without_database(&["migrate", "test", "--roundtrip"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("No migrations were applied"));
+
+    project.migration(
+        "20240101000000_users",
+        "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL);",
+        "DROP TABLE users;",
+    );
+    project.write(
+        "migrations/sqlite/tests/users.sql",
+        "INSERT INTO users (name) VALUES ('a;b');\nSELECT count(*) = 1 FROM users;",
+    );
+    project.write(
+        "migrations/sqlite/tests/empty.sql",
+        "SELECT count(*) = 0 FROM users;",
+    );
+
+    let output = project.run_without_database(&["migrate", "test", "--roundtrip"]);
+    assert!(output.status.success(), "{:?}", output);
+    let out = stdout(&output);
+    assert!(out.contains("Reverted every migration and applied them again"));
+    assert!(out.contains("ok      empty"));
+    assert!(out.contains("ok      users"));
+    assert!(out.contains("All 2 assertion(s) passed"));
+
+    // Every assertion sees the database as the migrations left it.
+    project.write(
+        "migrations/sqlite/tests/users.sql",
+        "SELECT count(*) FROM users;",
+    );
+    let output = project.run_without_database(&["migrate", "test"]);
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("FAILED  users: statement 1: returned a false row"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("1 of 2 assertion(s) failed"));
+    assert!(!project.table_exists("users"));
+}
+
+#[test]
+fn roundtrip_fails_when_down_migrations_leave_tables() {
+    let project = TestProject::new();
+    project.migration(
+        "20240101000000_users",
+        "CREATE TABLE users (id INTEGER PRIMARY KEY);",
+        "SELECT 1;",
+    );
+
+    let output = project.run_without_database(&["migrate", "test"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = project.run_without_database(&["migrate", "test", "--roundtrip"]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains(
+            "Reverting every migration did not restore the empty schema: left table users"
+        ),
+        "{:?}",
+        output
+    );
+}
+
+#[test]
+fn check_reports_table_conflicts_of_merged_branches() {
+    let project = TestProject::new();
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE users (id INTEGER PRIMARY KEY);",
+        "DROP TABLE users;",
+    );
+    project.migration(
+        "20250102000000_email",
+        "-- migrator:depends-on 20250101000000\nALTER TABLE users ADD COLUMN email TEXT;",
+        "ALTER TABLE users DROP COLUMN email;",
+    );
+    project.migration(
+        "20250103000000_name",
+        "-- migrator:depends-on 20250101000000\nALTER TABLE users ADD COLUMN name TEXT;",
+        "ALTER TABLE users DROP COLUMN name;",
+    );
+    let conflict =
+        "Migrations 20250102000000 and 20250103000000 both touch the following tables: users";
+
+    let output = project.run_without_database(&["migrate", "check"]);
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("[multiple-heads]"));
+    assert!(stdout(&output).contains(conflict));
+
+    let output = project.run_without_database(&["migrate", "merge"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = project.run_without_database(&["migrate", "check"]);
+    assert!(!output.status.success());
+    assert!(!stdout(&output).contains("[multiple-heads]"));
+    assert!(stdout(&output).contains(conflict), "{:?}", output);
+
+    // Against a database, the conflict matters while either migration is unapplied.
+    let output = project.run(&["migrate", "up", "20250102000000"]);
+    assert!(output.status.success(), "{:?}", output);
+    let output = project.run(&["migrate", "check"]);
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains(conflict), "{:?}", output);
+
+    let output = project.run(&["migrate", "up", "head"]);
+    assert!(output.status.success(), "{:?}", output);
+    let output = project.run(&["migrate", "check"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout(&output), "No problems found\n");
+}
+
+#[test]
+fn prints_the_history_graph_as_json_and_table() {
+    let project = TestProject::new();
+    project.migration("20250101000000_users", "SELECT 1;", "SELECT 1;");
+    project.migration("20250102000000_posts", "SELECT 2;", "SELECT 2;");
+    project.migration(
+        "20250103000000_tags",
+        "-- migrator:depends-on 20250101000000\nSELECT 3;",
+        "SELECT 3;",
+    );
+
+    let output = project.run(&["migrate", "up", "20250102000000"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = project.run(&["history", "--format", "json"]);
+    assert!(output.status.success(), "{:?}", output);
+    let history: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        history,
+        serde_json::json!({
+            "current": "20250102000000",
+            "heads": ["20250102000000", "20250103000000"],
+            "migrations": [
+                {
+                    "id": "20250103000000",
+                    "name": "tags",
+                    "depends_on": ["20250101000000"],
+                    "kind": "sql",
+                    "parents": ["20250101000000"],
+                },
+                {
+                    "id": "20250102000000",
+                    "name": "posts",
+                    "kind": "sql",
+                    "parents": ["20250101000000"],
+                },
+                {
+                    "id": "20250101000000",
+                    "name": "users",
+                    "kind": "sql",
+                    "parents": [],
+                },
+            ],
+        })
+    );
+
+    let output = project.run_without_database(&["migrate", "merge"]);
+    assert!(output.status.success(), "{:?}", output);
+    let merge = std::fs::read_dir(project.dir.path().join("migrations/sqlite/up"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .max()
+        .unwrap();
+    let merge = merge.trim_end_matches(".sql").split_once('_').unwrap();
+
+    let output = project.run(&["history", "--format", "table"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "ID              NAME   NOTES\n\
+             --------------  -----  ---------------------------------------\n\
+             {}  {}  merge of 20250102000000, 20250103000000\n\
+             20250103000000  tags\n\
+             20250102000000  posts  current\n\
+             20250101000000  users\n",
+            merge.0, merge.1
+        )
+    );
+}
+
+#[test]
+fn removes_the_temporary_test_database() {
+    let project = TestProject::new();
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE users (id INTEGER PRIMARY KEY);",
+        "DROP TABLE users;",
+    );
+    let temp = project.dir.path().join("tmp");
+    std::fs::create_dir(&temp).unwrap();
+
+    let run = || {
+        project
+            .command(&["migrate", "test", "--temp-file", "--roundtrip"])
+            .env("TMPDIR", &temp)
+            .output()
+            .unwrap()
+    };
+
+    let output = run();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(std::fs::read_dir(&temp).unwrap().count(), 0);
+
+    project.write("migrations/sqlite/tests/users.sql", "SELECT * FROM posts;");
+    let output = run();
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("FAILED  users: statement 1: no such table: posts"));
+    assert_eq!(std::fs::read_dir(&temp).unwrap().count(), 0);
+}
+
+#[cfg(feature = "sqlcipher")]
+#[test]
+fn reads_the_key_from_the_flag_the_environment_then_the_configuration() {
+    let project = TestProject::new();
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE users (id INTEGER PRIMARY KEY);",
+        "DROP TABLE users;",
+    );
+    project.write("right.key", "right\n");
+    project.write("wrong.key", "wrong\n");
+    let wrong_key = "Cannot read the database, is the key right?";
+    let status = |args: &[&str], env_key: Option<&str>| {
+        let mut command =
+            project.command(&[args, &["status", "-d", &project.database_path()]].concat());
+        if let Some(key) = env_key {
+            command.env("MIGRATOR_DB_KEY", key);
+        }
+        command.output().unwrap()
+    };
+
+    let output = project.run(&["migrate", "up", "head", "--key-file", "right.key"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(
+        !std::fs::read(project.database_path())
+            .unwrap()
+            .starts_with(b"SQLite format 3")
+    );
+
+    // The flag overrides the environment.
+    assert!(
+        status(&["--key-file", "right.key"], Some("wrong"))
+            .status
+            .success()
+    );
+    let output = status(&["--key-file", "wrong.key"], Some("right"));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains(wrong_key),
+        "{:?}",
+        output
+    );
+
+    // The environment overrides the configuration.
+    project.write("migrator.toml", "[sqlite]\nkey_file = \"wrong.key\"\n");
+    assert!(status(&[], Some("right")).status.success());
+    let output = status(&[], None);
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains(wrong_key),
+        "{:?}",
+        output
+    );
+
+    project.write("migrator.toml", "[sqlite]\nkey_file = \"right.key\"\n");
+    assert!(status(&[], None).status.success());
+    let output = status(&[], Some("wrong"));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains(wrong_key),
+        "{:?}",
+        output
+    );
+}
+
+#[cfg(feature = "sqlcipher")]
+#[test]
+fn rekeys_encrypted_databases() {
+    let project = TestProject::new();
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE users (id INTEGER PRIMARY KEY);",
+        "DROP TABLE users;",
+    );
+    project.write("old.key", "old");
+    project.write("new.key", "new");
+
+    let output = project.run(&["migrate", "up", "head", "--key-file", "old.key"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = project.run(&["rekey", "--key-file", "old.key"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--new-key-file"));
+
+    let output = project.run(&[
+        "rekey",
+        "--key-file",
+        "old.key",
+        "--new-key-file",
+        "new.key",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).starts_with("Changed the key of"));
+
+    assert!(
+        !project
+            .run(&["status", "--key-file", "old.key"])
+            .status
+            .success()
+    );
+    let output = project.run(&["migrate", "down", "base", "--key-file", "new.key"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    // Back to the old key, given in the environment this time.
+    let output = project
+        .command(&[
+            "rekey",
+            "-d",
+            &project.database_path(),
+            "--key-file",
+            "new.key",
+        ])
+        .env("MIGRATOR_DB_NEW_KEY", "old")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(
+        project
+            .run(&["status", "--key-file", "old.key"])
+            .status
+            .success()
+    );
+}
+
+#[test]
+fn migrates_the_databases_of_a_glob_and_of_a_list() {
+    let project = TestProject::new();
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE users (id INTEGER PRIMARY KEY);",
+        "DROP TABLE users;",
+    );
+    project.write("tenants/a.db", "");
+    project.write("tenants/b.db", "");
+    project.write("tenants/notes.txt", "not a database");
+    project.write(
+        "tenants.txt",
+        "# one tenant per line\n\ntenants/c.db\n   \n  # tenants/d.db\ntenants/a.db\n",
+    );
+
+    let output =
+        project.run_without_database(&["migrate", "up", "head", "--database-glob", "tenants/*.db"]);
+    assert!(output.status.success(), "{:?}", output);
+    let out = stdout(&output);
+    assert!(out.contains("tenants/a.db  base"), "{}", out);
+    assert!(out.contains("tenants/b.db  base"), "{}", out);
+    assert!(!out.contains("notes.txt"), "{}", out);
+    assert!(
+        out.ends_with("Migrated: 2, failed: 0, not run: 0\n"),
+        "{}",
+        out
+    );
+
+    let output =
+        project.run_without_database(&["migrate", "up", "head", "--database-list", "tenants.txt"]);
+    assert!(output.status.success(), "{:?}", output);
+    let out = stdout(&output);
+    assert!(out.contains("tenants/c.db  base"), "{}", out);
+    assert!(out.contains("tenants/a.db  20250101000000"), "{}", out);
+    assert!(!out.contains("d.db"), "{}", out);
+    assert!(
+        out.ends_with("Migrated: 2, failed: 0, not run: 0\n"),
+        "{}",
+        out
+    );
+    assert!(!project.dir.path().join("tenants/d.db").exists());
+
+    let output =
+        project.run_without_database(&["migrate", "up", "head", "--database-glob", "missing/*.db"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "No database file matches missing/*.db\n"
+    );
+
+    project.write("empty.txt", "# nothing yet\n\n");
+    let output =
+        project.run_without_database(&["migrate", "up", "head", "--database-list", "empty.txt"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Database list empty.txt is empty\n"
+    );
+}
+
+#[test]
+fn stops_at_the_first_failed_database_unless_keep_going() {
+    let project = TestProject::new();
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE users (id INTEGER PRIMARY KEY);",
+        "DROP TABLE users;",
+    );
+    project.write("tenants/a.db", "");
+    project.write(
+        "tenants/b.db",
+        "this file is not a SQLite database, it is long enough to tell",
+    );
+    project.write("tenants/c.db", "");
+
+    let args = [
+        "migrate",
+        "up",
+        "head",
+        "--database-glob",
+        "tenants/*.db",
+        "--jobs",
+        "1",
+    ];
+    let output = project.run_without_database(&args);
+    assert_eq!(output.status.code(), Some(1));
+    let out = stdout(&output);
+    assert!(out.contains("tenants/a.db  base"), "{}", out);
+    assert!(out.contains("failed: file is not a database"), "{}", out);
+    assert!(out.contains("tenants/c.db  -     -"), "{}", out);
+    assert!(out.contains("not run"), "{}", out);
+    assert!(
+        out.ends_with("Migrated: 1, failed: 1, not run: 1\n"),
+        "{}",
+        out
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Migrating 1 of 3 database(s) failed\n"
+    );
+
+    let output = project.run_without_database(&[&args[..], &["--keep-going"]].concat());
+    assert_eq!(output.status.code(), Some(1));
+    let out = stdout(&output);
+    assert!(out.contains("tenants/a.db  20250101000000"), "{}", out);
+    assert!(out.contains("tenants/c.db  base"), "{}", out);
+    assert!(!out.contains("not run\n"), "{}", out);
+    assert!(
+        out.ends_with("Migrated: 2, failed: 1, not run: 0\n"),
+        "{}",
+        out
+    );
+}
+
+#[test]
+fn reads_the_extensions_required_by_migrations() {
+    let project = TestProject::new();
+    project.migration(
+        "20250101000000_places",
+        "-- migrator:requires-extension mod_spatialite, vec0\n\
+         -- migrator:requires-extension fts_custom\n\
+         CREATE TABLE places (id INTEGER PRIMARY KEY);",
+        "DROP TABLE places;",
+    );
+
+    let output = project.run_without_database(&["migrate", "show", "20250101000000"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(
+        stdout(&output).contains("Requires extensions: mod_spatialite, vec0, fts_custom\n"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn loads_the_configured_extensions_before_those_of_the_migrations() {
+    let project = TestProject::new();
+    project.migration(
+        "20250101000000_places",
+        "-- migrator:requires-extension from_header\nCREATE TABLE places (id INTEGER PRIMARY KEY);",
+        "DROP TABLE places;",
+    );
+    project.write(
+        "migrator.toml",
+        "[sqlite]\nextensions = [\"from_config\"]\n",
+    );
+
+    let output = project.run(&["migrate", "up", "head"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    assert!(
+        stderr.starts_with("Cannot load SQLite extension from_config: "),
+        "{}",
+        stderr
+    );
+
+    project.write("migrator.toml", "");
+    let output = project.run(&["migrate", "up", "head"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    assert!(
+        stderr.starts_with(
+            "Migration 20250101000000 requires extension from_header\n\
+             Cannot load SQLite extension from_header: "
+        ),
+        "{}",
+        stderr
+    );
+    #[cfg(not(feature = "load-extension"))]
+    assert!(
+        stderr.ends_with(
+            "Loading extensions needs the migrator to be built with the load-extension feature\n"
+        ),
+        "{}",
+        stderr
+    );
+    assert!(!project.table_exists("places"));
+}
+
+/// The only test running the migrator in the test process, as the SQL functions are registered
+/// by the library user. It changes the working directory of the process to the project.
+#[test]
+fn creates_registered_sql_functions_for_migrations() {
+    use clap::Parser;
+    use migrator::{
+        Migrator,
+        cli::Cli,
+        rusqlite::functions::{Context, FunctionFlags},
+    };
+
+    let project = TestProject::new();
+    project.migration(
+        "20250101000000_posts",
+        "CREATE TABLE posts (title TEXT, slug TEXT);
+        INSERT INTO posts (title) VALUES ('Hello World');
+        UPDATE posts SET slug = app_slugify(title);",
+        "DROP TABLE posts;",
+    );
+
+    let migrator = Migrator::new().scalar_function(
+        "app_slugify",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |context: &Context<'_>| Ok(context.get::<String>(0)?.to_lowercase().replace(' ', "-")),
+    );
+    std::env::set_current_dir(project.dir.path()).unwrap();
+    let database_path = project.database_path();
+    migrator.run(Cli::parse_from([
+        "migrator",
+        "migrate",
+        "up",
+        "head",
+        "--database-url",
+        &database_path,
+    ]));
+
+    let slug = project
+        .connection()
+        .query_row("SELECT slug FROM posts", [], |row| row.get::<_, String>(0))
+        .unwrap();
+    assert_eq!(slug, "hello-world");
+}
+
+#[test]
+fn reruns_repeatable_migrations_after_versioned_ones_when_they_change() {
+    let project = TestProject::new();
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE users (id INTEGER PRIMARY KEY, active INTEGER);",
+        "DROP TABLE users;",
+    );
+    project.write(
+        "migrations/sqlite/repeatable/active_users.sql",
+        "DROP VIEW IF EXISTS active_users;
+        CREATE VIEW active_users AS SELECT id, email FROM users WHERE active = 1;",
+    );
+    // The view reads a column added by a later versioned migration, so it only works once all of
+    // them are applied.
+    project.migration(
+        "20250102000000_emails",
+        "ALTER TABLE users ADD COLUMN email TEXT;",
+        "ALTER TABLE users DROP COLUMN email;",
+    );
+
+    let output = project.run(&["migrate", "up", "head"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        stdout(&output),
+        "Applying migration: 20250101000000\n\
+         Applying migration: 20250102000000\n\
+         Applying repeatable migration: active_users\n"
+    );
+    assert_eq!(project.records("repeatable"), ["active_users"]);
+
+    let output = project.run(&["migrate", "up", "head"]);
+    assert_eq!(stdout(&output), "Already up to date\n");
+
+    // Unchanged, it is not applied again after new versioned migrations.
+    project.migration(
+        "20250103000000_names",
+        "ALTER TABLE users ADD COLUMN name TEXT;",
+        "ALTER TABLE users DROP COLUMN name;",
+    );
+    let output = project.run(&["migrate", "up", "head"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout(&output), "Applying migration: 20250103000000\n");
+
+    project.write(
+        "migrations/sqlite/repeatable/active_users.sql",
+        "DROP VIEW IF EXISTS active_users;
+        CREATE VIEW active_users AS SELECT id, email, name FROM users WHERE active = 1;",
+    );
+    let output = project.run(&["migrate", "up", "head"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        stdout(&output),
+        "Applying repeatable migration: active_users\n"
+    );
+    // Every application is recorded, the last one holds the checksum compared next time.
+    assert_eq!(
+        project.records("repeatable"),
+        ["active_users", "active_users"]
+    );
+    let columns = project
+        .connection()
+        .query_row(
+            "SELECT count(*) FROM pragma_table_info('active_users')",
+            [],
+            |row| row.get::<_, i64>(0),
+        )
+        .unwrap();
+    assert_eq!(columns, 3);
+}
+
+#[test]
+fn runs_each_seed_once_per_database_and_environment() {
+    let project = TestProject::new();
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT);",
+        "DROP TABLE users;",
+    );
+    project.write(
+        "migrations/sqlite/seeds/admin.sql",
+        "INSERT INTO users (email) VALUES ('admin@example.com');",
+    );
+    project.write(
+        "migrations/sqlite/seeds/fixtures.sql",
+        "-- migrator:env dev, test\nINSERT INTO users (email) VALUES ('dev@example.com');",
+    );
+    let users = || {
+        project
+            .connection()
+            .query_row("SELECT count(*) FROM users", [], |row| row.get::<_, i64>(0))
+            .unwrap()
+    };
+
+    let output = project.run(&["migrate", "up", "head"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = project.run(&["seed", "--env", "prod"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout(&output), "Seeding: admin\n");
+    assert_eq!(users(), 1);
+
+    let output = project.run(&["seed", "--env", "prod"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout(&output), "Nothing to seed\n");
+    assert_eq!(users(), 1);
+
+    let output = project.run(&["seed", "--env", "dev"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout(&output), "Seeding: fixtures\n");
+    assert_eq!(users(), 2);
+    let seeded = project
+        .connection()
+        .query_row("SELECT count(*) FROM __seeds__", [], |row| {
+            row.get::<_, i64>(0)
+        })
+        .unwrap();
+    assert_eq!(seeded, 2);
+
+    let output = project.run(&["seed", "admin", "--force"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout(&output), "Seeding: admin\n");
+    assert_eq!(users(), 3);
+
+    let output = project.run(&["seed", "--force", "--env", "prod"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout(&output), "Seeding: admin\n");
+    assert_eq!(users(), 4);
+}
+
+#[test]
+fn shows_renames_and_edits_migrations() {
+    let project = TestProject::new();
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE users (id INTEGER PRIMARY KEY);",
+        "DROP TABLE users;",
+    );
+    project.migration(
+        "20250102000000_posts",
+        "-- migrator:depends-on 20250101000000\n-- migrator:env dev\nSELECT 2;",
+        "SELECT 2;",
+    );
+    let path = |path: &str| project.dir.path().join(path);
+
+    let output = project.run_without_database(&["migrate", "show", "posts"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        stdout(&output),
+        "Migration: 20250102000000 posts\n\
+         Created: 2025-01-02 00:00:00\n\
+         Depends on: 20250101000000\n\
+         Environments: dev\n\
+         \n\
+         ==> migrations/sqlite/up/20250102000000_posts.sql <==\n\
+         -- migrator:depends-on 20250101000000\n-- migrator:env dev\nSELECT 2;\n\
+         ==> migrations/sqlite/down/20250102000000_posts.sql <==\n\
+         SELECT 2;"
+    );
+
+    let output = project.run(&["migrate", "up", "head"]);
+    assert!(output.status.success(), "{:?}", output);
+    let output = project.run(&["migrate", "rename", "users", "user accounts"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Migration 20250101000000 is recorded as applied in the database, use --force to rename \
+         it anyway\n"
+    );
+    assert!(path("migrations/sqlite/up/20250101000000_users.sql").exists());
+
+    let output = project.run(&["migrate", "rename", "users", "user accounts", "--force"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        stdout(&output),
+        "Renamed migrations/sqlite/up/20250101000000_users.sql to \
+         migrations/sqlite/up/20250101000000_user_accounts.sql\n\
+         Renamed migrations/sqlite/down/20250101000000_users.sql to \
+         migrations/sqlite/down/20250101000000_user_accounts.sql\n"
+    );
+    assert!(!path("migrations/sqlite/up/20250101000000_users.sql").exists());
+    assert!(!path("migrations/sqlite/down/20250101000000_users.sql").exists());
+    assert_eq!(
+        std::fs::read_to_string(path(
+            "migrations/sqlite/down/20250101000000_user_accounts.sql"
+        ))
+        .unwrap(),
+        "DROP TABLE users;"
+    );
+    // The children depend on the id, which is kept, so their headers stay as they are.
+    assert_eq!(
+        std::fs::read_to_string(path("migrations/sqlite/up/20250102000000_posts.sql")).unwrap(),
+        "-- migrator:depends-on 20250101000000\n-- migrator:env dev\nSELECT 2;"
+    );
+    let output = project.run(&["migrate", "up", "head"]);
+    assert_eq!(stdout(&output), "Already up to date\n");
+
+    project.write("editor.sh", "#!/bin/sh\necho \"$@\" > edited.txt\n");
+    let editor = path("editor.sh");
+    std::fs::set_permissions(&editor, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+    let output = project
+        .command(&["migrate", "edit", "user_accounts"])
+        .env_remove("VISUAL")
+        .env("EDITOR", &editor)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        std::fs::read_to_string(path("edited.txt")).unwrap(),
+        "migrations/sqlite/up/20250101000000_user_accounts.sql \
+         migrations/sqlite/down/20250101000000_user_accounts.sql\n"
+    );
+
+    let output = project
+        .command(&["migrate", "edit", "user_accounts"])
+        .env_remove("VISUAL")
+        .env("EDITOR", "false")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Editor 'false' exited with exit status: 1\n"
+    );
+}
+
+#[test]
+fn ignores_hidden_and_other_files_of_the_migrations_directories() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let project = TestProject::new();
+    project.migration(
+        "20250101000000_users",
+        "CREATE TABLE users (id INTEGER PRIMARY KEY);",
+        "DROP TABLE users;",
+    );
+    // Left behind by an interrupted write of a new migration.
+    project.write(
+        "migrations/sqlite/up/.20250102000000_posts.sql.1234.0.tmp",
+        "CREATE TABLE posts (id INTEGER PRIMARY KEY);",
+    );
+    project.write("migrations/sqlite/up/README.md", "Migrations of the app");
+    project.write("migrations/sqlite/down/.DS_Store", "");
+    let non_utf8 = std::ffi::OsStr::from_bytes(b"20250103000000_\xff.sql");
+    std::fs::write(
+        project
+            .dir
+            .path()
+            .join("migrations/sqlite/up")
+            .join(non_utf8),
+        "SELECT 1;",
+    )
+    .unwrap();
+
+    let output = project.run(&["migrate", "up", "head"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout(&output), "Applying migration: 20250101000000\n");
+    assert!(!project.table_exists("posts"));
+}